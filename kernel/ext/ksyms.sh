@@ -0,0 +1,34 @@
+#!/bin/sh
+# Prints the symbol table embedded in the kernel's `.ksyms` section: the line
+# `KSYMS`, then `<address> <name>` for every function in the ELF file, sorted
+# by address, padded with NUL bytes to the size of the section.
+#
+# Usage: ksyms.sh <nm> <elf> <size>
+
+set -e
+
+NM="$1"
+ELF="$2"
+SIZE="$3"
+
+if [ -z "$SIZE" ]; then
+    echo "ksyms.sh: $ELF has no .ksyms section" >&2
+    exit 1
+fi
+
+TABLE="$(mktemp)"
+trap 'rm -f "$TABLE"' EXIT
+
+printf 'KSYMS\n' > "$TABLE"
+"$NM" -n -C --defined-only "$ELF" \
+    | awk '$2 ~ /^[tTwW]$/ { addr = $1; $1 = ""; $2 = ""; sub(/^  /, ""); print addr " " $0 }' \
+    >> "$TABLE"
+
+LENGTH="$(wc -c < "$TABLE")"
+if [ "$LENGTH" -ge "$SIZE" ]; then
+    echo "ksyms.sh: symbol table is $LENGTH bytes, larger than $SIZE" >&2
+    exit 1
+fi
+
+cat "$TABLE"
+head -c "$((SIZE - LENGTH))" /dev/zero
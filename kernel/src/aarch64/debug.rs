@@ -0,0 +1,306 @@
+//! Self-hosted debug support: software stepping and hardware breakpoints and
+//! watchpoints.
+
+/// `MDSCR_EL1.SS`: enables software step exceptions.
+const MDSCR_SS: u64 = 1 << 0;
+
+/// `SPSR_ELx.SS`: the software step state `eret` restores into `PSTATE`.
+/// While it is set, exactly one instruction executes before a software step
+/// exception is taken.
+pub const SPSR_SS: u64 = 1 << 21;
+
+/// `SPSR_ELx.D`: debug exceptions masked. Code at EL1 can only be stepped
+/// with them unmasked.
+pub const SPSR_D: u64 = 1 << 9;
+
+/// Returns the monitor debug system control register.
+#[inline(always)]
+unsafe fn mdscr() -> u64 {
+    let mdscr: u64;
+    asm!("mrs $0, MDSCR_EL1" : "=r"(mdscr));
+    mdscr
+}
+
+/// Sets the monitor debug system control register.
+#[inline(always)]
+unsafe fn set_mdscr(mdscr: u64) {
+    asm!("msr MDSCR_EL1, $0
+          isb"
+         :: "r"(mdscr) :: "volatile");
+}
+
+/// Clears the OS lock. Out of reset the OS lock is set, which masks every
+/// debug exception.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn unlock_os() {
+    asm!("msr OSLAR_EL1, xzr
+          isb"
+         :::: "volatile");
+}
+
+/// Arranges for a software step exception to be taken after the next
+/// instruction of the lower exception level that `spsr` returns to.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1, with `spsr` being
+/// the saved program status register the next `eret` restores.
+pub unsafe fn enable_step(spsr: &mut u64) {
+    unlock_os();
+    set_mdscr(mdscr() | MDSCR_SS);
+    *spsr |= SPSR_SS;
+}
+
+/// Like `enable_step()`, for code at EL1: also enables debug exceptions
+/// taken from EL1 and unmasks them in `spsr`. The caller is responsible for
+/// masking them again, if they were, once stepping stops.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1, with `spsr` being
+/// the saved program status register the next `eret` restores.
+pub unsafe fn enable_kernel_step(spsr: &mut u64) {
+    unlock_os();
+    set_mdscr(mdscr() | MDSCR_SS | MDSCR_KDE);
+    *spsr = (*spsr | SPSR_SS) & !SPSR_D;
+}
+
+/// Stops software stepping.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1, with `spsr` being
+/// the saved program status register the next `eret` restores.
+pub unsafe fn disable_step(spsr: &mut u64) {
+    set_mdscr(mdscr() & !MDSCR_SS);
+    *spsr &= !SPSR_SS;
+}
+
+/// Enables software step exceptions if the code `spsr` returns to is being
+/// stepped, and disables them otherwise. `MDSCR_EL1.SS` applies to whatever
+/// runs next, so the scheduler calls this on every switch: another process
+/// entered with it set would take a step exception right away.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1, with `spsr` being
+/// the saved program status register the next `eret` restores.
+pub unsafe fn restore_step(spsr: u64) {
+    match spsr & SPSR_SS {
+        0 => set_mdscr(mdscr() & !MDSCR_SS),
+        _ => {
+            unlock_os();
+            set_mdscr(mdscr() | MDSCR_SS)
+        }
+    }
+}
+
+/// `MDSCR_EL1.MDE`: enables breakpoint and watchpoint exceptions.
+const MDSCR_MDE: u64 = 1 << 15;
+
+/// `MDSCR_EL1.KDE`: enables debug exceptions taken from EL1 to EL1.
+const MDSCR_KDE: u64 = 1 << 13;
+
+/// The most breakpoints and watchpoints this module manages; the Cortex-A53
+/// implements exactly these many.
+const MAX_BREAKPOINTS: usize = 6;
+const MAX_WATCHPOINTS: usize = 4;
+
+/// Control register bits shared by breakpoints and watchpoints: enabled (`E`)
+/// and matching at both EL0 and EL1 (`PMC`/`PAC` = `0b11`).
+const CTRL_ENABLE: u64 = 1;
+const CTRL_EL0_EL1: u64 = 0b11 << 1;
+
+/// `DBGBCR.BAS`: match the whole A64 instruction.
+const BCR_BAS: u64 = 0b1111 << 5;
+
+/// `DBGWCR.BAS`: watch all eight bytes of the doubleword.
+const WCR_BAS: u64 = 0xff << 5;
+
+/// Defines `$read(n)` and `$write(n, value)` accessing the `n`th register of
+/// a numbered family of debug registers.
+macro debug_registers($read:ident, $write:ident, $($n:tt => $reg:expr),*) {
+    unsafe fn $read(n: usize) -> u64 {
+        let value: u64;
+        match n {
+            $($n => asm!(concat!("mrs $0, ", $reg) : "=r"(value)),)*
+            _ => panic!("no debug register {}", n)
+        }
+        value
+    }
+
+    unsafe fn $write(n: usize, value: u64) {
+        match n {
+            $($n => asm!(concat!("msr ", $reg, ", $0
+                                  isb") :: "r"(value) :: "volatile"),)*
+            _ => panic!("no debug register {}", n)
+        }
+    }
+}
+
+debug_registers!(read_bvr, write_bvr, 0 => "DBGBVR0_EL1", 1 => "DBGBVR1_EL1", 2 => "DBGBVR2_EL1",
+                 3 => "DBGBVR3_EL1", 4 => "DBGBVR4_EL1", 5 => "DBGBVR5_EL1");
+debug_registers!(read_bcr, write_bcr, 0 => "DBGBCR0_EL1", 1 => "DBGBCR1_EL1", 2 => "DBGBCR2_EL1",
+                 3 => "DBGBCR3_EL1", 4 => "DBGBCR4_EL1", 5 => "DBGBCR5_EL1");
+debug_registers!(read_wvr, write_wvr, 0 => "DBGWVR0_EL1", 1 => "DBGWVR1_EL1", 2 => "DBGWVR2_EL1",
+                 3 => "DBGWVR3_EL1");
+debug_registers!(read_wcr, write_wcr, 0 => "DBGWCR0_EL1", 1 => "DBGWCR1_EL1", 2 => "DBGWCR2_EL1",
+                 3 => "DBGWCR3_EL1");
+
+/// The accesses a watchpoint triggers on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    /// `DBGWCR.LSC`, the load/store control of a watchpoint.
+    fn lsc(&self) -> u64 {
+        match *self {
+            Access::Read => 0b01 << 3,
+            Access::Write => 0b10 << 3,
+            Access::ReadWrite => 0b11 << 3,
+        }
+    }
+
+    fn from_lsc(wcr: u64) -> Access {
+        match (wcr >> 3) & 0b11 {
+            0b01 => Access::Read,
+            0b10 => Access::Write,
+            _ => Access::ReadWrite
+        }
+    }
+}
+
+/// Returns the number of hardware breakpoints available.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn breakpoints() -> usize {
+    let dfr0: u64;
+    asm!("mrs $0, ID_AA64DFR0_EL1" : "=r"(dfr0));
+    ::std::cmp::min(((dfr0 >> 12) & 0xf) as usize + 1, MAX_BREAKPOINTS)
+}
+
+/// Returns the number of hardware watchpoints available.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn watchpoints() -> usize {
+    let dfr0: u64;
+    asm!("mrs $0, ID_AA64DFR0_EL1" : "=r"(dfr0));
+    ::std::cmp::min(((dfr0 >> 20) & 0xf) as usize + 1, MAX_WATCHPOINTS)
+}
+
+/// Enables breakpoint and watchpoint exceptions from EL0 and EL1. They are
+/// taken to EL1 as `Syndrome::Breakpoint` and `Syndrome::Watchpoint`.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn enable() {
+    unlock_os();
+    set_mdscr(mdscr() | MDSCR_MDE | MDSCR_KDE);
+    // Unmask debug exceptions at EL1
+    asm!("msr DAIFClr, #0b1000" :::: "volatile");
+}
+
+/// Sets a breakpoint on the instruction at `addr`, which is rounded down to
+/// a multiple of 4. Returns the number of the breakpoint, or `None` if all
+/// are in use.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn set_breakpoint(addr: u64) -> Option<usize> {
+    let n = (0..breakpoints()).find(|&n| read_bcr(n) & CTRL_ENABLE == 0)?;
+    enable();
+    write_bvr(n, addr & !0b11);
+    write_bcr(n, BCR_BAS | CTRL_EL0_EL1 | CTRL_ENABLE);
+    Some(n)
+}
+
+/// Clears breakpoint `n`. Returns `false` if it was not set.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn clear_breakpoint(n: usize) -> bool {
+    if breakpoint(n).is_none() {
+        return false;
+    }
+
+    write_bcr(n, 0);
+    true
+}
+
+/// Returns the address of breakpoint `n`, if it is set.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn breakpoint(n: usize) -> Option<u64> {
+    if n >= breakpoints() || read_bcr(n) & CTRL_ENABLE == 0 {
+        return None;
+    }
+
+    Some(read_bvr(n))
+}
+
+/// Sets a watchpoint on the 8-byte aligned doubleword containing `addr`,
+/// triggering on `access`. Returns the number of the watchpoint, or `None`
+/// if all are in use.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn set_watchpoint(addr: u64, access: Access) -> Option<usize> {
+    let n = (0..watchpoints()).find(|&n| read_wcr(n) & CTRL_ENABLE == 0)?;
+    enable();
+    write_wvr(n, addr & !0b111);
+    write_wcr(n, WCR_BAS | access.lsc() | CTRL_EL0_EL1 | CTRL_ENABLE);
+    Some(n)
+}
+
+/// Clears watchpoint `n`. Returns `false` if it was not set.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn clear_watchpoint(n: usize) -> bool {
+    if watchpoint(n).is_none() {
+        return false;
+    }
+
+    write_wcr(n, 0);
+    true
+}
+
+/// Returns the address and access kind of watchpoint `n`, if it is set.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn watchpoint(n: usize) -> Option<(u64, Access)> {
+    if n >= watchpoints() {
+        return None;
+    }
+
+    let wcr = read_wcr(n);
+    if wcr & CTRL_ENABLE == 0 {
+        return None;
+    }
+
+    Some((read_wvr(n), Access::from_lsc(wcr)))
+}
+
+/// Disables all breakpoints and watchpoints without forgetting them, so that
+/// the instruction that hit one can execute. `resume_all()` enables them
+/// again.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn suspend_all() {
+    set_mdscr(mdscr() & !MDSCR_MDE);
+}
+
+/// Enables the breakpoints and watchpoints disabled by `suspend_all()`.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+pub unsafe fn resume_all() {
+    set_mdscr(mdscr() | MDSCR_MDE);
+}
@@ -0,0 +1,156 @@
+pub mod debug;
+
+/// Returns the current stack pointer.
+#[inline(always)]
+pub fn sp() -> *const u8 {
+    let ptr: usize;
+    unsafe {
+        asm!("mov $0, sp" : "=r"(ptr));
+    }
+
+    ptr as *const u8
+}
+
+/// Returns the current frame pointer (`x29`): the address of the frame record
+/// of the calling function.
+#[inline(always)]
+pub fn fp() -> u64 {
+    let fp: u64;
+    unsafe {
+        asm!("mov $0, x29" : "=r"(fp));
+    }
+
+    fp
+}
+
+/// Returns the current exception level.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn current_el() -> u8 {
+    let el_reg: u64;
+    asm!("mrs $0, CurrentEL" : "=r"(el_reg));
+    ((el_reg & 0b1100) >> 2) as u8
+}
+
+/// Returns the fault address register: the address that caused the last
+/// instruction abort, data abort, PC alignment fault or watchpoint taken to
+/// EL1.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn far() -> u64 {
+    let far: u64;
+    asm!("mrs $0, FAR_EL1" : "=r"(far));
+    far
+}
+
+/// Returns the count of the generic timer's physical counter.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn counter() -> u64 {
+    let count: u64;
+    asm!("isb
+          mrs $0, CNTPCT_EL0" : "=r"(count) ::: "volatile");
+    count
+}
+
+/// Returns the frequency of the generic timer's counter in Hz.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn counter_frequency() -> u64 {
+    let frequency: u64;
+    asm!("mrs $0, CNTFRQ_EL0" : "=r"(frequency));
+    frequency
+}
+
+/// `CPACR_EL1.FPEN` bit 21: if clear, FP/SIMD instructions at EL0 trap to
+/// EL1 (`Syndrome::SimdFp`). EL1 access is always enabled (bit 20).
+const CPACR_FPEN_EL0: u64 = 1 << 21;
+
+/// Returns `true` if EL0 may use FP/SIMD registers without trapping.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn user_fp_enabled() -> bool {
+    let cpacr: u64;
+    asm!("mrs $0, CPACR_EL1" : "=r"(cpacr));
+    cpacr & CPACR_FPEN_EL0 != 0
+}
+
+/// Enables or disables EL0 access to FP/SIMD registers.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn set_user_fp(enabled: bool) {
+    let mut cpacr: u64;
+    asm!("mrs $0, CPACR_EL1" : "=r"(cpacr));
+    if enabled {
+        cpacr |= CPACR_FPEN_EL0;
+    } else {
+        cpacr &= !CPACR_FPEN_EL0;
+    }
+    asm!("msr CPACR_EL1, $0
+          isb" :: "r"(cpacr) :: "volatile");
+}
+
+/// Returns the SPSel value.
+#[inline(always)]
+pub fn sp_sel() -> u8 {
+    let ptr: u32;
+    unsafe {
+        asm!("mrs $0, SPSel" : "=r"(ptr));
+    }
+
+    (ptr & 1) as u8
+}
+
+#[inline(always)]
+pub fn wait_for_interrupt() {
+    unsafe {
+        asm!("wfi");
+    }
+}
+
+/// Returns the core currently executing.
+///
+/// # Safety
+///
+/// This function should only be called when EL is >= 1.
+pub unsafe fn affinity() -> usize {
+    let x: usize;
+    asm!("mrs     $0, mpidr_el1
+          and     $0, $0, #3"
+          : "=r"(x));
+
+    x
+}
+
+/// A NOOP that won't be optimized out.
+pub fn nop() {
+    unsafe {
+        asm!("nop" :::: "volatile");
+    }
+}
+
+/// Makes instructions written as data visible to instruction fetches, e.g.
+/// after setting a software breakpoint.
+///
+/// # Safety
+///
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn sync_icache() {
+    asm!("dsb ish
+          ic iallu
+          dsb ish
+          isb" :::: "volatile");
+}
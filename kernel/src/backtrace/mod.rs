@@ -0,0 +1,180 @@
+//! Backtraces from the frame pointer (`x29`) chain, symbolized against the
+//! symbol table embedded in the kernel image.
+//!
+//! Every function built with frame pointers pushes a frame record, the
+//! caller's `x29` followed by its return address, and points `x29` at it.
+//! Following the records from `x29` yields the return addresses of all
+//! callers.
+//!
+//! The symbol table is generated from the linked kernel by `ext/ksyms.sh` and
+//! written over the `.ksyms` section reserved by `KSYMS`: the line `KSYMS`
+//! followed by one `<hex address> <name>` line per function, sorted by
+//! address and padded with NUL bytes.
+
+#[cfg(test)]
+mod tests;
+
+use std::{slice, str};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use console::kprintln;
+use traps::TrapFrame;
+
+/// The size of the `.ksyms` section. The `Makefile` reads it from the linked
+/// kernel.
+pub const KSYMS_SIZE: usize = 256 * 1024;
+
+/// The first line of a symbol table.
+const MAGIC: &'static [u8; 6] = b"KSYMS\n";
+
+/// The most frames printed in a backtrace.
+const MAX_DEPTH: usize = 32;
+
+/// Set once the backtrace of trapped code was printed. A kernel fault then
+/// panics, and its backtrace would only repeat the callers.
+static TRAPPED: AtomicBool = AtomicBool::new(false);
+
+/// The space reserved for the symbol table.
+#[repr(C)]
+pub struct Ksyms {
+    magic: [u8; 6],
+    symbols: [u8; KSYMS_SIZE - 6],
+}
+
+/// The symbol table, filled in after linking. It is mutable so that reads
+/// are not folded to the placeholder's contents.
+#[link_section = ".ksyms"]
+#[no_mangle]
+pub static mut KSYMS: Ksyms = Ksyms { magic: *MAGIC, symbols: [0; KSYMS_SIZE - 6] };
+
+/// Returns the embedded symbol table, without the padding.
+fn table() -> &'static [u8] {
+    let bytes = unsafe { slice::from_raw_parts(&KSYMS as *const Ksyms as *const u8, KSYMS_SIZE) };
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+/// Returns the name of the function containing `addr` and the offset of
+/// `addr` into it, looked up in the symbol table `table`.
+pub fn symbolize(table: &[u8], addr: u64) -> Option<(&str, u64)> {
+    if !table.starts_with(MAGIC) {
+        return None;
+    }
+
+    let mut found = None;
+    for line in table[MAGIC.len()..].split(|&b| b == b'\n') {
+        let mut fields = line.splitn(2, |&b| b == b' ');
+        let start = fields.next()
+            .and_then(|s| str::from_utf8(s).ok())
+            .and_then(|s| u64::from_str_radix(s, 16).ok());
+        let name = fields.next().and_then(|s| str::from_utf8(s).ok());
+
+        match (start, name) {
+            // The table is sorted, so the last symbol at or below `addr` wins
+            (Some(start), Some(name)) if start <= addr => found = Some((name, addr - start)),
+            (Some(_), Some(_)) => break,
+            _ => continue
+        }
+    }
+
+    found.map(|(name, offset)| (strip_hash(name), offset))
+}
+
+/// Strips the `::h<16 hex digits>` hash from a demangled Rust symbol.
+fn strip_hash(name: &str) -> &str {
+    let len = name.len();
+    if len > 19 && name.is_char_boundary(len - 19) {
+        let (path, hash) = name.split_at(len - 19);
+        if hash.starts_with("::h") && hash[3..].bytes().all(|b| (b as char).is_digit(16)) {
+            return path;
+        }
+    }
+    name
+}
+
+/// The return addresses found by following a chain of frame records.
+#[derive(Debug)]
+pub struct Frames {
+    fp: u64,
+    depth: usize,
+}
+
+/// Returns the return addresses of the callers whose frame records are
+/// chained from `fp`, innermost first.
+pub fn frames(fp: u64) -> Frames {
+    Frames { fp, depth: 0 }
+}
+
+impl Iterator for Frames {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.depth >= MAX_DEPTH || !valid_record(self.fp) {
+            return None;
+        }
+
+        let (next, lr) = unsafe {
+            let record = self.fp as *const u64;
+            (*record, *record.offset(1))
+        };
+
+        // Stacks grow down, so callers' records are at higher addresses; a
+        // record anywhere else ends the chain
+        self.fp = if next > self.fp { next } else { 0 };
+        self.depth += 1;
+        match lr {
+            0 => None,
+            lr => Some(lr)
+        }
+    }
+}
+
+/// Whether a frame record may be read at `fp`: records are 16-byte aligned
+/// and, on the Pi, in RAM.
+#[cfg(not(test))]
+fn valid_record(fp: u64) -> bool {
+    use pi::common::IO_BASE;
+    fp != 0 && fp % 16 == 0 && fp.saturating_add(16) <= IO_BASE as u64
+}
+
+/// Host tests build their records in their own memory.
+#[cfg(test)]
+fn valid_record(fp: u64) -> bool {
+    fp != 0 && fp % 16 == 0
+}
+
+/// Prints one line of a backtrace. Return addresses point after the call,
+/// so the call itself (`addr - 4`) is symbolized.
+fn print_frame(n: usize, addr: u64, call: u64) {
+    match symbolize(table(), call) {
+        Some((name, offset)) => kprintln!("  #{:<2} {:#018x} {}+{:#x}", n, addr, name, offset + addr - call),
+        None => kprintln!("  #{:<2} {:#018x} ??", n, addr)
+    }
+}
+
+/// Prints a backtrace of the callers chained from `fp`.
+pub fn print(fp: u64) {
+    kprintln!("backtrace:");
+    for (n, lr) in frames(fp).enumerate() {
+        print_frame(n, lr, lr.saturating_sub(4));
+    }
+}
+
+/// Prints a backtrace of the code trapped in `tf`: its `pc`, then its
+/// callers.
+pub fn print_trapped(tf: &TrapFrame) {
+    TRAPPED.store(true, Ordering::SeqCst);
+    kprintln!("backtrace:");
+    print_frame(0, tf.elr(), tf.elr());
+    for (n, lr) in frames(tf.x(29)).enumerate() {
+        print_frame(n + 1, lr, lr.saturating_sub(4));
+    }
+}
+
+/// Prints a backtrace of a panic from `fp`, unless the panic follows a kernel
+/// fault whose backtrace `print_trapped()` already printed.
+pub fn print_panic(fp: u64) {
+    if !TRAPPED.load(Ordering::SeqCst) {
+        print(fp);
+    }
+}
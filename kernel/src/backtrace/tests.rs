@@ -0,0 +1,98 @@
+use backtrace::{frames, symbolize};
+
+const TABLE: &'static [u8] = b"KSYMS
+0000000000080000 _start
+0000000000080100 kernel::kmain::h0123456789abcdef
+0000000000080200 <kernel::mutex::Mutex<T>>::lock::hfedcba9876543210
+0000000000080300 memcpy
+";
+
+#[test]
+fn symbolize_addresses() {
+    assert_eq!(symbolize(TABLE, 0x80000), Some(("_start", 0)));
+    assert_eq!(symbolize(TABLE, 0x80124), Some(("kernel::kmain", 0x24)));
+    assert_eq!(symbolize(TABLE, 0x80208), Some(("<kernel::mutex::Mutex<T>>::lock", 8)));
+    assert_eq!(symbolize(TABLE, 0x80400), Some(("memcpy", 0x100)));
+    assert_eq!(symbolize(TABLE, 0x7fffc), None);
+}
+
+#[test]
+fn symbolize_without_table() {
+    assert_eq!(symbolize(b"", 0x80000), None);
+    assert_eq!(symbolize(b"KSYMS\n", 0x80000), None);
+    assert_eq!(symbolize(b"0000000000080000 _start\n", 0x80000), None);
+}
+
+#[test]
+fn symbolize_keeps_other_suffixes() {
+    let table = b"KSYMS\n0000000000080000 kernel::h0123\n0000000000080100 a::hxyz0123456789abcd\n";
+    assert_eq!(symbolize(table, 0x80000), Some(("kernel::h0123", 0)));
+    assert_eq!(symbolize(table, 0x80100), Some(("a::hxyz0123456789abcd", 0)));
+}
+
+#[repr(align(16))]
+struct Stack([u64; 8]);
+
+#[test]
+fn walk_frame_records() {
+    let mut stack = Stack([0; 8]);
+    let base = stack.0.as_ptr() as u64;
+
+    // Three records, each pointing at its caller's, the outermost ending the
+    // chain with a null frame pointer
+    stack.0[0] = base + 16;
+    stack.0[1] = 0x8_1004;
+    stack.0[2] = base + 48;
+    stack.0[3] = 0x8_2008;
+    stack.0[6] = 0;
+    stack.0[7] = 0x8_300c;
+
+    let lrs: Vec<u64> = frames(base).collect();
+    assert_eq!(lrs, vec![0x8_1004, 0x8_2008, 0x8_300c]);
+}
+
+#[test]
+fn walk_stops_at_bad_records() {
+    let mut stack = Stack([0; 8]);
+    let base = stack.0.as_ptr() as u64;
+
+    // A record pointing back down the stack
+    stack.0[2] = base;
+    stack.0[3] = 0x8_1004;
+    assert_eq!(frames(base + 16).collect::<Vec<_>>(), vec![0x8_1004]);
+
+    // A misaligned or null frame pointer
+    assert_eq!(frames(base + 8).count(), 0);
+    assert_eq!(frames(0).count(), 0);
+
+    // A record without a return address
+    stack.0[0] = base + 16;
+    stack.0[1] = 0;
+    assert_eq!(frames(base).count(), 0);
+}
+
+#[test]
+fn walk_stops_at_cycles() {
+    let mut stack = Stack([0; 8]);
+    let base = stack.0.as_ptr() as u64;
+
+    // A record pointing at itself would loop forever
+    stack.0[0] = base;
+    stack.0[1] = 0x8_1004;
+    assert_eq!(frames(base).count(), 1);
+}
+
+#[repr(align(16))]
+struct DeepStack([u64; 128]);
+
+#[test]
+fn walk_is_bounded() {
+    let mut stack = DeepStack([0; 128]);
+    let base = stack.0.as_ptr() as u64;
+    for i in 0..63 {
+        stack.0[2 * i] = base + 16 * (i as u64 + 1);
+        stack.0[2 * i + 1] = 0x8_0004;
+    }
+
+    assert_eq!(frames(base).count(), 32);
+}
@@ -4,16 +4,47 @@ use std::fmt;
 use pi::uart::MiniUart;
 
 use mutex::Mutex;
+use process::Id;
+
+// ASCII control characters sent by Ctrl-C and Ctrl-Z
+const CTRL_C: u8 = 0x03;
+const CTRL_Z: u8 = 0x1a;
+
+// The number of received bytes buffered until they are read
+const INPUT_SIZE: usize = 64;
+
+/// A job control request typed at the console.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Signal {
+    /// Ctrl-C: terminate the foreground job.
+    Interrupt,
+    /// Ctrl-Z: stop the foreground job.
+    Stop,
+}
 
 /// A global singleton allowing read/write access to the console.
 pub struct Console {
-    inner: Option<MiniUart>
+    inner: Option<MiniUart>,
+    input: [u8; INPUT_SIZE],
+    head: usize,
+    len: usize,
+    signal: Option<Signal>,
+    foreground: Option<Id>,
+    reserved: bool,
 }
 
 impl Console {
     /// Creates a new instance of `Console`.
     const fn new() -> Console {
-        Console { inner: None }
+        Console {
+            inner: None,
+            input: [0; INPUT_SIZE],
+            head: 0,
+            len: 0,
+            signal: None,
+            foreground: None,
+            reserved: false
+        }
     }
 
     /// Initializes the console if it's not already initialized.
@@ -37,12 +68,114 @@ impl Console {
         }
     }
 
+    /// Moves the bytes received by the UART device into the input buffer,
+    /// taking out Ctrl-C and Ctrl-Z as the pending job control request. They
+    /// are dropped while there is no foreground process, as are bytes that do
+    /// not fit. While the console is reserved, Ctrl-C is GDB's request to
+    /// stop the target and Ctrl-Z is input.
+    fn receive(&mut self) {
+        while self.inner().has_byte() {
+            let control = !self.reserved && self.foreground.is_some();
+            match self.inner().read_byte() {
+                CTRL_C if self.reserved || control => self.signal = Some(Signal::Interrupt),
+                CTRL_Z if control => self.signal = Some(Signal::Stop),
+                CTRL_C | CTRL_Z if !self.reserved => (),
+                byte if self.len < INPUT_SIZE => {
+                    self.input[(self.head + self.len) % INPUT_SIZE] = byte;
+                    self.len += 1;
+                },
+                _ => ()
+            }
+        }
+    }
+
+    /// Removes the oldest byte from the input buffer.
+    fn pop_input(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let byte = self.input[self.head];
+        self.head = (self.head + 1) % INPUT_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+
     /// Reads a byte from the UART device, blocking until a byte is available.
+    /// Buffered input is read first. Control characters are returned as is.
     pub fn read_byte(&mut self) -> u8 {
-        self.inner().read_byte()
+        match self.pop_input() {
+            Some(byte) => byte,
+            None => self.inner().read_byte()
+        }
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. While
+    /// the console is reserved, there never is.
+    pub fn has_byte(&mut self) -> bool {
+        !self.reserved && (self.len > 0 || self.inner().has_byte())
+    }
+
+    /// Reserves the UART device for the GDB stub, or releases it. While it is
+    /// reserved, only `read_byte()` and `write_byte()` use it: reads and
+    /// writes of processes fail with `WouldBlock`, so they wait until it is
+    /// released, and kernel output is discarded.
+    pub fn set_reserved(&mut self, reserved: bool) {
+        self.reserved = reserved;
+    }
+
+    /// Receives the bytes the UART device has, then returns and clears the
+    /// pending job control request, if Ctrl-C or Ctrl-Z was received.
+    pub fn take_signal(&mut self) -> Option<Signal> {
+        self.receive();
+        self.signal.take()
+    }
+
+    /// Returns the process Ctrl-C and Ctrl-Z are delivered to, if any.
+    pub fn foreground(&self) -> Option<Id> {
+        self.foreground
+    }
+
+    /// Makes `id` the process Ctrl-C and Ctrl-Z are delivered to, or none.
+    /// Requests received before are forgotten.
+    pub fn set_foreground(&mut self, id: Option<Id>) {
+        self.receive();
+        self.signal = None;
+        self.foreground = id;
+    }
+
+    /// Raises `Interrupt::Aux` whenever a byte is received, until it is read
+    /// or moved into the input buffer.
+    pub fn enable_receive_interrupt(&mut self) {
+        self.inner().enable_receive_interrupt();
     }
 
-    /// Writes the byte `byte` to the UART device.
+    /// Reads the bytes that are already available into `buf` without
+    /// blocking. Returns an error of kind `WouldBlock` if there are none.
+    /// Ctrl-C and Ctrl-Z are not read: they are left for `take_signal()`.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.receive();
+        if self.reserved {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "console reserved"));
+        }
+
+        let mut read = 0;
+        while read < buf.len() {
+            match self.pop_input() {
+                Some(byte) => buf[read] = byte,
+                None => break
+            }
+            read += 1;
+        }
+
+        if read == 0 && buf.len() > 0 {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "no input"));
+        }
+
+        Ok(read)
+    }
+
+    /// Writes the byte `byte` to the UART device, even while it is reserved.
     pub fn write_byte(&mut self, byte: u8) {
         self.inner().write_byte(byte);
     }
@@ -56,6 +189,10 @@ impl io::Read for Console {
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.reserved {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "console reserved"));
+        }
+
         self.inner().write(buf)
     }
 
@@ -67,6 +204,10 @@ impl io::Write for Console {
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         use std::fmt::Write;
+        if self.reserved {
+            return Ok(());
+        }
+
         let result = self.inner().write_str(s);
         match result {
             Ok(_) => Ok(()),
@@ -0,0 +1,464 @@
+//! A stub for the GDB remote serial protocol over the console UART.
+//!
+//! A session starts with `attach()`, e.g. from the shell's `gdb <pid>`
+//! command, which stops the process `pid` at its next instruction. While one
+//! is attached, every debug exception (`brk`, completed steps and hardware
+//! breakpoint and watchpoint hits) stops in the stub instead of the debug
+//! shell, as does Ctrl-C sent by GDB while the target runs, and a host `gdb`
+//! connected to the serial port takes control:
+//!
+//! ```text
+//! (gdb) target remote /dev/ttyUSB0
+//! ```
+//!
+//! The stub supports reading and writing registers from the `TrapFrame`,
+//! memory in RAM, software (`Z0`) and hardware (`Z1`-`Z4`) breakpoints,
+//! continue and single step, in user code and in the kernel.
+
+mod packet;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::ptr;
+
+use aarch64;
+use aarch64::debug::{self, Access};
+use console::CONSOLE;
+use mutex::Mutex;
+use pi::common::IO_BASE;
+use process::Id;
+use shell::Resume;
+use traps::{self, Info, TrapFrame};
+use self::packet::{Decoder, Event};
+use SCHEDULER;
+
+/// `brk #0`, written over instructions to set software breakpoints.
+const BRK: u32 = 0xd420_0000;
+
+/// The largest packet the stub accepts and sends, advertised in reply to
+/// `qSupported`.
+const PACKET_SIZE: u64 = 0x1000;
+
+/// The stop reply: stopped by `SIGTRAP`.
+const STOPPED: &'static [u8] = b"S05";
+
+/// The reply to a request that succeeded.
+const OK: &'static [u8] = b"OK";
+
+/// `E01`: a breakpoint or watchpoint could not be set or cleared.
+const FAILED: &'static [u8] = b"E01";
+
+/// `E0e` (`EFAULT`): memory outside of RAM.
+const FAULT: &'static [u8] = b"E0e";
+
+/// `E16` (`EINVAL`): a malformed request.
+const INVALID: &'static [u8] = b"E16";
+
+/// The registers in GDB's AArch64 numbering, past `x0`-`x30`.
+const SP: usize = 31;
+const PC: usize = 32;
+const CPSR: usize = 33;
+const V0: usize = 34;
+const FPSR: usize = 66;
+const FPCR: usize = 67;
+
+/// The attached session, if any.
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Starts a session on the process `id`, which is single stepped so that it
+/// stops in the stub after its next instruction. Returns `false` if there is
+/// no such process, if it is terminated or if it is the current one, whose
+/// state is not in its trap frame.
+///
+/// The console UART is reserved for the session until it ends, so that
+/// output of processes and the kernel does not get mixed into its packets.
+pub fn attach(id: Id) -> bool {
+    if SCHEDULER.current() == Some(id) {
+        return false;
+    }
+
+    let stopping = SCHEDULER.with_process(id, |p| {
+        if p.is_zombie() {
+            return false;
+        }
+        // The scheduler enables stepping when it switches the process in
+        let spsr = p.trap_frame.spsr();
+        p.trap_frame.set_spsr(spsr | debug::SPSR_SS);
+        true
+    });
+    if stopping != Some(true) {
+        return false;
+    }
+
+    let mut session = SESSION.lock();
+    if session.is_none() {
+        *session = Some(Session::new());
+        CONSOLE.lock().set_reserved(true);
+    }
+    true
+}
+
+/// Returns `true` if a session is attached.
+pub fn attached() -> bool {
+    SESSION.lock().is_some()
+}
+
+/// Returns `true` if the instruction at `addr` is a software breakpoint set
+/// by GDB. Execution stops at, not after, such a breakpoint, and GDB removes
+/// it before resuming.
+pub fn is_breakpoint(addr: u64) -> bool {
+    match *SESSION.lock() {
+        Some(ref session) => session.breakpoints.contains_key(&addr),
+        None => false
+    }
+}
+
+/// Reports the stop of the code trapped in `tf` by the exception described
+/// by `info` to GDB and serves its requests until it resumes execution, which
+/// this function returns. If GDB detaches, the session ends and execution
+/// continues.
+pub fn stop(info: Info, tf: &mut TrapFrame) -> Resume {
+    let mut session = SESSION.lock();
+    let resume = match *session {
+        Some(ref mut session) => session.serve(info, tf),
+        None => return Resume::Continue
+    };
+
+    match resume {
+        Some(resume) => resume,
+        None => {
+            if let Some(mut session) = session.take() {
+                session.remove_breakpoints();
+                CONSOLE.lock().set_reserved(false);
+            }
+            Resume::Continue
+        }
+    }
+}
+
+/// How the stub answers a packet.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Send a reply and wait for the next packet.
+    Reply(Vec<u8>),
+    /// Resume execution; the stop is reported later.
+    Resume(Resume),
+    /// End the session and continue.
+    Detach,
+}
+
+fn reply(data: &[u8]) -> Action {
+    Action::Reply(data.to_vec())
+}
+
+/// The state of a session: the instructions replaced by software
+/// breakpoints, by address.
+#[derive(Debug)]
+struct Session {
+    breakpoints: BTreeMap<u64, u32>,
+    /// The end of the memory GDB may access.
+    limit: u64,
+}
+
+impl Session {
+    /// Returns a session that may access RAM, like the debug shell.
+    fn new() -> Session {
+        Session::with_limit(IO_BASE as u64)
+    }
+
+    /// Returns a session that may access memory below `limit`.
+    fn with_limit(limit: u64) -> Session {
+        Session { breakpoints: BTreeMap::new(), limit }
+    }
+
+    /// Sends the stop reply and serves packets. Returns how to resume, or
+    /// `None` if GDB detached.
+    fn serve(&mut self, info: Info, tf: &mut TrapFrame) -> Option<Resume> {
+        let mut decoder = Decoder::new();
+        let mut last = packet::frame(STOPPED);
+        send(&last);
+
+        loop {
+            let byte = CONSOLE.lock().read_byte();
+            match decoder.feed(byte) {
+                Some(Event::Packet(data)) => {
+                    send(b"+");
+                    match self.handle(info, tf, &data) {
+                        Action::Reply(data) => {
+                            last = packet::frame(&data);
+                            send(&last);
+                        },
+                        Action::Resume(resume) => return Some(resume),
+                        Action::Detach => {
+                            send(&packet::frame(OK));
+                            return None;
+                        }
+                    }
+                },
+                Some(Event::Corrupt) => send(b"-"),
+                Some(Event::Nack) => send(&last),
+                // Already stopped
+                Some(Event::Interrupt) => {
+                    last = packet::frame(STOPPED);
+                    send(&last);
+                },
+                Some(Event::Ack) | None => ()
+            }
+        }
+    }
+
+    /// Answers the packet `data` for the code trapped in `tf` by the
+    /// exception described by `info`.
+    fn handle(&mut self, info: Info, tf: &mut TrapFrame, data: &[u8]) -> Action {
+        if data.is_empty() {
+            return reply(b"");
+        }
+
+        let (command, args) = (data[0], &data[1..]);
+        match command {
+            b'?' => reply(STOPPED),
+            b'g' => {
+                let mut out = Vec::new();
+                for n in 0..(FPCR + 1) {
+                    push_register(&mut out, info, tf, n);
+                }
+                Action::Reply(out)
+            },
+            b'G' => reply(write_registers(info, tf, args).map_or(INVALID, |_| OK)),
+            b'p' => match parse_hex(args) {
+                Some(n) if (n as usize) <= FPCR => {
+                    let mut out = Vec::new();
+                    push_register(&mut out, info, tf, n as usize);
+                    Action::Reply(out)
+                },
+                _ => reply(INVALID)
+            },
+            b'P' => {
+                let mut parts = args.splitn(2, |&b| b == b'=');
+                let n = parts.next().and_then(parse_hex);
+                let value = parts.next().and_then(packet::parse_le);
+                match (n, value) {
+                    (Some(n), Some(value)) => {
+                        reply(if set_register(info, tf, n as usize, value) { OK } else { INVALID })
+                    },
+                    _ => reply(INVALID)
+                }
+            },
+            b'm' => match parse_range(args) {
+                Some((addr, len)) if accessible(addr, len, self.limit) => {
+                    // Two hex digits per byte; GDB asks again for the rest
+                    let len = ::std::cmp::min(len, PACKET_SIZE / 2);
+                    let mut out = Vec::new();
+                    for i in 0..len {
+                        let byte = unsafe { ptr::read_volatile((addr + i) as *const u8) };
+                        packet::push_hex(&mut out, &[byte]);
+                    }
+                    Action::Reply(out)
+                },
+                Some(_) => reply(FAULT),
+                None => reply(INVALID)
+            },
+            b'M' => {
+                let mut parts = args.splitn(2, |&b| b == b':');
+                let range = parts.next().and_then(parse_range);
+                let bytes = parts.next().and_then(packet::decode_hex);
+                match (range, bytes) {
+                    (Some((addr, len)), Some(ref bytes)) if bytes.len() as u64 == len => {
+                        if !accessible(addr, len, self.limit) {
+                            return reply(FAULT);
+                        }
+                        for (i, &byte) in bytes.iter().enumerate() {
+                            unsafe { ptr::write_volatile((addr + i as u64) as *mut u8, byte) };
+                        }
+                        unsafe { aarch64::sync_icache() };
+                        reply(OK)
+                    },
+                    _ => reply(INVALID)
+                }
+            },
+            b'c' | b's' => {
+                if !args.is_empty() {
+                    match parse_hex(args) {
+                        Some(addr) => tf.set_elr(addr),
+                        None => return reply(INVALID)
+                    }
+                }
+                Action::Resume(if command == b'c' { Resume::Continue } else { Resume::Step })
+            },
+            b'Z' | b'z' => self.breakpoint(command == b'Z', args),
+            b'D' | b'k' => Action::Detach,
+            b'H' | b'T' => reply(OK),
+            b'q' if args.starts_with(b"Supported") => {
+                reply(format!("PacketSize={:x}", PACKET_SIZE).as_bytes())
+            },
+            b'q' if args.starts_with(b"Attached") => reply(b"1"),
+            _ => reply(b"")
+        }
+    }
+
+    /// Handles `Z<type>,<addr>,<kind>` (set if `set`) and `z...` (clear).
+    fn breakpoint(&mut self, set: bool, args: &[u8]) -> Action {
+        let mut parts = args.splitn(2, |&b| b == b',');
+        let kind = parts.next().and_then(parse_hex);
+        let addr = parts.next()
+            .and_then(|rest| rest.splitn(2, |&b| b == b',').next())
+            .and_then(parse_hex);
+
+        let (kind, addr) = match (kind, addr) {
+            (Some(kind), Some(addr)) => (kind, addr),
+            _ => return reply(INVALID)
+        };
+
+        let done = match kind {
+            0 if set => self.insert(addr),
+            0 => self.remove(addr),
+            1 if set => unsafe { debug::set_breakpoint(addr).is_some() },
+            1 => unsafe {
+                (0..debug::breakpoints())
+                    .find(|&n| debug::breakpoint(n) == Some(addr & !0b11))
+                    .map_or(false, |n| debug::clear_breakpoint(n))
+            },
+            2...4 => {
+                let access = match kind {
+                    2 => Access::Write,
+                    3 => Access::Read,
+                    _ => Access::ReadWrite
+                };
+                unsafe {
+                    if set {
+                        debug::set_watchpoint(addr, access).is_some()
+                    } else {
+                        (0..debug::watchpoints())
+                            .find(|&n| debug::watchpoint(n) == Some((addr & !0b111, access)))
+                            .map_or(false, |n| debug::clear_watchpoint(n))
+                    }
+                }
+            },
+            // Unsupported types are reported with an empty reply
+            _ => return reply(b"")
+        };
+
+        reply(if done { OK } else { FAILED })
+    }
+
+    /// Writes a `brk` over the instruction at `addr`.
+    fn insert(&mut self, addr: u64) -> bool {
+        if addr % 4 != 0 || !accessible(addr, 4, self.limit) {
+            return false;
+        }
+
+        if !self.breakpoints.contains_key(&addr) {
+            let insn = unsafe { ptr::read_volatile(addr as *const u32) };
+            self.breakpoints.insert(addr, insn);
+            unsafe {
+                ptr::write_volatile(addr as *mut u32, BRK);
+                aarch64::sync_icache();
+            }
+        }
+        true
+    }
+
+    /// Restores the instruction replaced by the breakpoint at `addr`.
+    fn remove(&mut self, addr: u64) -> bool {
+        match self.breakpoints.remove(&addr) {
+            Some(insn) => {
+                unsafe {
+                    ptr::write_volatile(addr as *mut u32, insn);
+                    aarch64::sync_icache();
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    fn remove_breakpoints(&mut self) {
+        let addrs: Vec<u64> = self.breakpoints.keys().cloned().collect();
+        for addr in addrs {
+            self.remove(addr);
+        }
+    }
+}
+
+fn send(bytes: &[u8]) {
+    let mut console = CONSOLE.lock();
+    for &byte in bytes {
+        console.write_byte(byte);
+    }
+}
+
+fn parse_hex(s: &[u8]) -> Option<u64> {
+    packet::parse_hex(s)
+}
+
+/// Parses `<addr>,<len>`.
+fn parse_range(s: &[u8]) -> Option<(u64, u64)> {
+    let mut parts = s.splitn(2, |&b| b == b',');
+    let addr = parts.next().and_then(parse_hex)?;
+    let len = parts.next().and_then(parse_hex)?;
+    Some((addr, len))
+}
+
+/// Whether GDB may access the `len` bytes at `addr`: they must not be null
+/// and must end at `limit` at the latest.
+fn accessible(addr: u64, len: u64, limit: u64) -> bool {
+    addr != 0 && addr.checked_add(len).map_or(false, |end| end <= limit)
+}
+
+/// Appends register `n` in GDB's numbering. `fpsr` and `fpcr` are not saved
+/// in the trap frame and are sent as unavailable.
+fn push_register(out: &mut Vec<u8>, info: Info, tf: &TrapFrame, n: usize) {
+    match n {
+        0...30 => packet::push_le(out, tf.x(n), 8),
+        SP => packet::push_le(out, traps::faulting_sp(info, tf), 8),
+        PC => packet::push_le(out, tf.elr(), 8),
+        CPSR => packet::push_le(out, tf.spsr(), 4),
+        FPSR | FPCR => out.extend_from_slice(b"xxxxxxxx"),
+        _ => {
+            let q = tf.q(n - V0);
+            packet::push_le(out, q as u64, 8);
+            packet::push_le(out, (q >> 64) as u64, 8);
+        }
+    }
+}
+
+/// Sets register `n` in GDB's numbering. Returns `false` if it cannot be set.
+/// The stack pointer of kernel code is not in the trap frame, so it cannot be
+/// changed.
+fn set_register(info: Info, tf: &mut TrapFrame, n: usize, value: u64) -> bool {
+    match n {
+        0...30 => tf.set_x(n, value),
+        SP if info.is_lower() => tf.stack_pointer = value,
+        PC => tf.set_elr(value),
+        CPSR => tf.set_spsr(value),
+        _ => return false
+    }
+    true
+}
+
+/// Handles the body of a `G` packet: `x0`-`x30`, `sp`, `pc` and `cpsr`.
+/// Any SIMD registers that follow are ignored. The stack pointer of kernel
+/// code must be left as it is; nothing is written otherwise.
+fn write_registers(info: Info, tf: &mut TrapFrame, s: &[u8]) -> Option<()> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    for n in 0..(CPSR + 1) {
+        let digits = if n == CPSR { 8 } else { 16 };
+        if offset + digits > s.len() {
+            return None;
+        }
+        values.push(packet::parse_le(&s[offset..offset + digits])?);
+        offset += digits;
+    }
+
+    if !info.is_lower() && values[SP] != traps::faulting_sp(info, tf) {
+        return None;
+    }
+
+    for (n, &value) in values.iter().enumerate() {
+        set_register(info, tf, n, value);
+    }
+    Some(())
+}
@@ -0,0 +1,199 @@
+//! Framing and hex encoding of GDB remote serial protocol packets, which are
+//! sent as `$<data>#<checksum>` and acknowledged with `+` or `-`.
+
+/// Sent by GDB to interrupt a running target.
+pub const INTERRUPT: u8 = 0x03;
+
+const HEX: &'static [u8; 16] = b"0123456789abcdef";
+
+/// Returns the checksum of packet data: the sum of its bytes modulo 256.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Frames `data` as a packet, escaping the bytes that have a meaning in the
+/// protocol (`$`, `#`, `}` and `*`).
+pub fn frame(data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len());
+    for &byte in data {
+        match byte {
+            b'$' | b'#' | b'}' | b'*' => {
+                body.push(b'}');
+                body.push(byte ^ 0x20);
+            },
+            _ => body.push(byte)
+        }
+    }
+
+    let sum = checksum(&body);
+    let mut packet = Vec::with_capacity(body.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(&body);
+    packet.push(b'#');
+    push_hex(&mut packet, &[sum]);
+    packet
+}
+
+/// What a `Decoder` recognized in the bytes received from GDB.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A packet with a valid checksum, unescaped.
+    Packet(Vec<u8>),
+    /// A packet with an invalid checksum; GDB should be sent a `-`.
+    Corrupt,
+    /// GDB acknowledged the last packet.
+    Ack,
+    /// GDB asks for the last packet to be sent again.
+    Nack,
+    /// GDB asks the target to stop.
+    Interrupt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Idle,
+    Data,
+    Escape,
+    Checksum(usize, u8),
+}
+
+/// Decodes the byte stream received from GDB into packets, one byte at a
+/// time.
+#[derive(Debug)]
+pub struct Decoder {
+    state: State,
+    data: Vec<u8>,
+    sum: u8,
+}
+
+impl Decoder {
+    /// Returns a new decoder waiting for the start of a packet.
+    pub fn new() -> Decoder {
+        Decoder { state: State::Idle, data: Vec::new(), sum: 0 }
+    }
+
+    /// Feeds `byte` to the decoder. Returns the event it completes, if any.
+    /// Bytes outside of packets other than acknowledgements and interrupts
+    /// are ignored.
+    pub fn feed(&mut self, byte: u8) -> Option<Event> {
+        match self.state {
+            State::Idle => match byte {
+                b'$' => {
+                    self.state = State::Data;
+                    self.data.clear();
+                    self.sum = 0;
+                },
+                b'+' => return Some(Event::Ack),
+                b'-' => return Some(Event::Nack),
+                INTERRUPT => return Some(Event::Interrupt),
+                _ => ()
+            },
+            State::Data => match byte {
+                b'#' => self.state = State::Checksum(0, 0),
+                // An unterminated packet restarts
+                b'$' => {
+                    self.data.clear();
+                    self.sum = 0;
+                },
+                b'}' => {
+                    self.sum = self.sum.wrapping_add(byte);
+                    self.state = State::Escape;
+                },
+                _ => {
+                    self.sum = self.sum.wrapping_add(byte);
+                    self.data.push(byte);
+                }
+            },
+            State::Escape => {
+                self.sum = self.sum.wrapping_add(byte);
+                self.data.push(byte ^ 0x20);
+                self.state = State::Data;
+            },
+            State::Checksum(digits, value) => {
+                let value = match hex_digit(byte) {
+                    Some(digit) => (value << 4) | digit,
+                    None => {
+                        self.state = State::Idle;
+                        return Some(Event::Corrupt);
+                    }
+                };
+
+                if digits == 0 {
+                    self.state = State::Checksum(1, value);
+                    return None;
+                }
+
+                self.state = State::Idle;
+                if value != self.sum {
+                    return Some(Event::Corrupt);
+                }
+
+                let data = ::std::mem::replace(&mut self.data, Vec::new());
+                return Some(Event::Packet(data));
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the value of the hex digit `byte`.
+pub fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'...b'9' => Some(byte - b'0'),
+        b'a'...b'f' => Some(byte - b'a' + 10),
+        b'A'...b'F' => Some(byte - b'A' + 10),
+        _ => None
+    }
+}
+
+/// Parses a hex number, most significant digit first, as used for addresses
+/// and lengths.
+pub fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+
+    s.iter().fold(Some(0), |value, &byte| {
+        value.and_then(|v| hex_digit(byte).map(|d| (v << 4) | d as u64))
+    })
+}
+
+/// Decodes hex digit pairs into bytes.
+pub fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(s.len() / 2);
+    for pair in s.chunks(2) {
+        bytes.push((hex_digit(pair[0])? << 4) | hex_digit(pair[1])?);
+    }
+    Some(bytes)
+}
+
+/// Appends `bytes` as hex digit pairs.
+pub fn push_hex(out: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        out.push(HEX[(byte >> 4) as usize]);
+        out.push(HEX[(byte & 0xf) as usize]);
+    }
+}
+
+/// Appends the low `size` bytes of `value` in target (little endian) byte
+/// order, as registers are transferred.
+pub fn push_le(out: &mut Vec<u8>, value: u64, size: usize) {
+    for i in 0..size {
+        push_hex(out, &[(value >> (8 * i)) as u8]);
+    }
+}
+
+/// Parses a register value transferred in target (little endian) byte order.
+pub fn parse_le(s: &[u8]) -> Option<u64> {
+    let bytes = decode_hex(s)?;
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+
+    Some(bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u64))
+}
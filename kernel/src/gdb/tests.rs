@@ -0,0 +1,251 @@
+mod packet {
+    use gdb::packet::{self, Decoder, Event};
+
+    fn decode(bytes: &[u8]) -> Vec<Event> {
+        let mut decoder = Decoder::new();
+        bytes.iter().filter_map(|&byte| decoder.feed(byte)).collect()
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(packet::checksum(b""), 0);
+        assert_eq!(packet::checksum(b"OK"), 0x9a);
+        assert_eq!(packet::checksum(&[0xff, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn frame() {
+        assert_eq!(packet::frame(b"OK"), b"$OK#9a".to_vec());
+        assert_eq!(packet::frame(b""), b"$#00".to_vec());
+        assert_eq!(packet::frame(b"a#b"), b"$a}\x03b#43".to_vec());
+    }
+
+    #[test]
+    fn decode_packets() {
+        assert_eq!(decode(b"$OK#9a"), vec![Event::Packet(b"OK".to_vec())]);
+        assert_eq!(decode(b"+$g#67-"),
+                   vec![Event::Ack, Event::Packet(b"g".to_vec()), Event::Nack]);
+        assert_eq!(decode(b"junk\x03"), vec![Event::Interrupt]);
+    }
+
+    #[test]
+    fn decode_corrupt() {
+        assert_eq!(decode(b"$OK#9b"), vec![Event::Corrupt]);
+        assert_eq!(decode(b"$OK#zz"), vec![Event::Corrupt]);
+        assert_eq!(decode(b"$OK#9b$OK#9a"),
+                   vec![Event::Corrupt, Event::Packet(b"OK".to_vec())]);
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let data = b"$#}*plain";
+        assert_eq!(decode(&packet::frame(data)), vec![Event::Packet(data.to_vec())]);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(packet::parse_hex(b"0"), Some(0));
+        assert_eq!(packet::parse_hex(b"80000"), Some(0x80000));
+        assert_eq!(packet::parse_hex(b"ffffffffffffffff"), Some(!0));
+        assert_eq!(packet::parse_hex(b"10000000000000000"), None);
+        assert_eq!(packet::parse_hex(b""), None);
+        assert_eq!(packet::parse_hex(b"12g"), None);
+
+        assert_eq!(packet::decode_hex(b"00ff1A"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(packet::decode_hex(b"abc"), None);
+
+        let mut out = Vec::new();
+        packet::push_le(&mut out, 0x1122_3344, 8);
+        assert_eq!(out, b"4433221100000000".to_vec());
+        assert_eq!(packet::parse_le(&out), Some(0x1122_3344));
+        assert_eq!(packet::parse_le(b""), None);
+    }
+}
+
+mod stub {
+    use gdb::{accessible, Action, Session};
+    use pi::common::IO_BASE;
+    use shell::Resume;
+    use traps::{Info, Source, Kind, TrapFrame};
+
+    /// A stop of a process, whose stack pointer is in the trap frame.
+    fn lower() -> Info {
+        Info::new(Source::LowerAArch64, Kind::Synchronous)
+    }
+
+    /// A stop of the kernel, whose trap frame sits right below its stack.
+    fn kernel() -> Info {
+        Info::new(Source::CurrentSpElx, Kind::Synchronous)
+    }
+
+    /// Answers `data` in a session that may access all of memory: host
+    /// tests access their own, which lies above `IO_BASE`.
+    fn handle(tf: &mut TrapFrame, data: &[u8]) -> Action {
+        Session::with_limit(!0).handle(lower(), tf, data)
+    }
+
+    fn text(action: Action) -> String {
+        match action {
+            Action::Reply(data) => String::from_utf8(data).expect("ascii"),
+            other => panic!("expected a reply, got {:?}", other)
+        }
+    }
+
+    fn frame() -> TrapFrame {
+        let mut tf = TrapFrame::default();
+        for n in 0..31 {
+            tf.set_x(n, n as u64);
+        }
+        tf.stack_pointer = 0x8_0000;
+        tf.set_elr(0x8_1234);
+        tf.set_spsr(0x3c5);
+        tf
+    }
+
+    #[test]
+    fn stop_reason() {
+        assert_eq!(text(handle(&mut frame(), b"?")), "S05");
+    }
+
+    #[test]
+    fn read_registers() {
+        let reply = text(handle(&mut frame(), b"g"));
+        // 31 x registers, sp and pc, cpsr, 32 v registers, fpsr and fpcr
+        assert_eq!(reply.len(), 2 * (33 * 8 + 4 + 32 * 16 + 2 * 4));
+        assert_eq!(&reply[0..16], "0000000000000000");
+        assert_eq!(&reply[16..32], "0100000000000000");
+        assert_eq!(&reply[31 * 16..32 * 16], "0000080000000000");
+        assert_eq!(&reply[32 * 16..33 * 16], "3412080000000000");
+        assert_eq!(&reply[33 * 16..33 * 16 + 8], "c5030000");
+        assert!(reply.ends_with("xxxxxxxxxxxxxxxx"));
+    }
+
+    #[test]
+    fn single_registers() {
+        let mut tf = frame();
+        assert_eq!(text(handle(&mut tf, b"p1e")), "1e00000000000000");
+        assert_eq!(text(handle(&mut tf, b"p20")), "3412080000000000");
+        assert_eq!(text(handle(&mut tf, b"p44")), "E16");
+
+        assert_eq!(text(handle(&mut tf, b"P5=efbeadde00000000")), "OK");
+        assert_eq!(tf.x(5), 0xdead_beef);
+        assert_eq!(text(handle(&mut tf, b"P20=0010080000000000")), "OK");
+        assert_eq!(tf.elr(), 0x8_1000);
+        assert_eq!(text(handle(&mut tf, b"P42=00000000")), "E16");
+    }
+
+    #[test]
+    fn write_registers() {
+        let mut tf = frame();
+        let mut data = b"G".to_vec();
+        for n in 0..33u8 {
+            data.extend_from_slice(format!("{:02x}00000000000000", n + 1).as_bytes());
+        }
+        data.extend_from_slice(b"05000000");
+
+        assert_eq!(text(handle(&mut tf, &data)), "OK");
+        assert_eq!(tf.x(0), 1);
+        assert_eq!(tf.lr(), 31);
+        assert_eq!(tf.stack_pointer, 32);
+        assert_eq!(tf.elr(), 33);
+        assert_eq!(tf.spsr(), 5);
+
+        assert_eq!(text(handle(&mut tf, b"G0011")), "E16");
+    }
+
+    #[test]
+    fn kernel_stack_pointer() {
+        let mut tf = frame();
+        let sp = &tf as *const TrapFrame as u64 + TrapFrame::SIZE as u64;
+        let hex = |value: u64| {
+            (0..8).map(|i| format!("{:02x}", (value >> (8 * i)) as u8)).collect::<String>()
+        };
+
+        let mut session = Session::new();
+        let reply = match session.handle(kernel(), &mut tf, b"p1f") {
+            Action::Reply(data) => String::from_utf8(data).expect("ascii"),
+            other => panic!("expected a reply, got {:?}", other)
+        };
+        assert_eq!(reply, hex(sp));
+
+        // It cannot be changed, alone or with the other registers
+        let request = format!("P1f={}", hex(sp + 16));
+        assert_eq!(session.handle(kernel(), &mut tf, request.as_bytes()), Action::Reply(b"E16".to_vec()));
+        let mut data = b"G".to_vec();
+        for n in 0..33u64 {
+            data.extend_from_slice(hex(if n == 31 { sp + 16 } else { 0x100 + n }).as_bytes());
+        }
+        data.extend_from_slice(b"05000000");
+        assert_eq!(session.handle(kernel(), &mut tf, &data), Action::Reply(b"E16".to_vec()));
+        assert_eq!(tf.x(1), 1);
+
+        // Writing it back unchanged is fine
+        data[1 + 31 * 16..1 + 32 * 16].copy_from_slice(hex(sp).as_bytes());
+        assert_eq!(session.handle(kernel(), &mut tf, &data), Action::Reply(b"OK".to_vec()));
+        assert_eq!(tf.x(1), 0x101);
+        assert_eq!(tf.elr(), 0x120);
+        assert_eq!(tf.stack_pointer, 0x8_0000);
+    }
+
+    #[test]
+    fn read_memory() {
+        let memory = [0xde_u8, 0xad, 0xbe, 0xef];
+        let addr = memory.as_ptr() as usize;
+
+        let request = format!("m{:x},4", addr);
+        assert_eq!(text(handle(&mut frame(), request.as_bytes())), "deadbeef");
+        assert_eq!(text(handle(&mut frame(), b"m0,4")), "E0e");
+        assert_eq!(text(handle(&mut frame(), b"m10")), "E16");
+    }
+
+    #[test]
+    fn memory_limit() {
+        let end = IO_BASE as u64;
+        assert!(accessible(end - 4, 4, end));
+        assert!(!accessible(end - 2, 4, end));
+        assert!(!accessible(end, 1, end));
+        assert!(!accessible(0, 4, end));
+        assert!(!accessible(!0, 2, !0));
+
+        // A session on the Pi only touches RAM
+        let mut session = Session::new();
+        let mut tf = frame();
+        let mut request = |data: String| match session.handle(lower(), &mut tf, data.as_bytes()) {
+            Action::Reply(data) => String::from_utf8(data).expect("ascii"),
+            other => panic!("expected a reply, got {:?}", other)
+        };
+        assert_eq!(request(format!("m{:x},4", end - 2)), "E0e");
+        assert_eq!(request(format!("M{:x},1:00", end)), "E0e");
+        assert_eq!(request(format!("Z0,{:x},4", end)), "E01");
+    }
+
+    #[test]
+    fn read_memory_fits_in_a_packet() {
+        let memory = [0x5a_u8; 0x1000];
+        let request = format!("m{:x},1000", memory.as_ptr() as usize);
+        let reply = text(handle(&mut frame(), request.as_bytes()));
+        assert_eq!(reply.len(), 0x1000);
+    }
+
+    #[test]
+    fn resume() {
+        let mut tf = frame();
+        assert_eq!(handle(&mut tf, b"c"), Action::Resume(Resume::Continue));
+        assert_eq!(handle(&mut tf, b"s"), Action::Resume(Resume::Step));
+        assert_eq!(tf.elr(), 0x8_1234);
+        assert_eq!(handle(&mut tf, b"c81000"), Action::Resume(Resume::Continue));
+        assert_eq!(tf.elr(), 0x8_1000);
+        assert_eq!(handle(&mut tf, b"D"), Action::Detach);
+    }
+
+    #[test]
+    fn queries() {
+        let mut tf = frame();
+        assert_eq!(text(handle(&mut tf, b"qSupported:swbreak+")), "PacketSize=1000");
+        assert_eq!(text(handle(&mut tf, b"Hg0")), "OK");
+        assert_eq!(text(handle(&mut tf, b"vMustReplyEmpty")), "");
+        assert_eq!(text(handle(&mut tf, b"Z9,1000,4")), "");
+        assert_eq!(text(handle(&mut tf, b"Z0,1002,4")), "E01");
+        assert_eq!(text(handle(&mut tf, b"Z0")), "E16");
+    }
+}
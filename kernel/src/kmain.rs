@@ -22,6 +22,7 @@ extern crate alloc;
 extern crate pi;
 extern crate stack_vec;
 extern crate fat32;
+extern crate ulib;
 
 pub mod allocator;
 pub mod lang_items;
@@ -30,14 +31,16 @@ pub mod console;
 pub mod shell;
 pub mod fs;
 pub mod traps;
+pub mod gdb;
 pub mod aarch64;
+pub mod backtrace;
 pub mod process;
 pub mod vm;
 
 #[cfg(not(test))]
 use allocator::Allocator;
 use fs::FileSystem;
-use process::GlobalScheduler;
+use process::{GlobalScheduler, GlobalFutexes};
 
 #[cfg(not(test))]
 #[global_allocator]
@@ -47,6 +50,8 @@ pub static FILE_SYSTEM: FileSystem = FileSystem::uninitialized();
 
 pub static SCHEDULER: GlobalScheduler = GlobalScheduler::uninitialized();
 
+pub static FUTEXES: GlobalFutexes = GlobalFutexes::uninitialized();
+
 #[no_mangle]
 #[cfg(not(test))]
 pub extern "C" fn kmain() {
@@ -84,8 +89,8 @@ pub extern "C" fn start_shell() {
 pub extern "C" fn start_test_process() {
     let mut i = 0;
     loop {
-        pi::timer::spin_sleep_ms(200);
-        console::kprintln!("test {}", i);
+        ulib::sleep(200);
+        ulib::println!("test {}", i);
         i += 1;
     }
 }
@@ -95,8 +100,8 @@ pub extern "C" fn start_test_process() {
 pub extern "C" fn start_test_process_2() {
     let mut i = 0;
     loop {
-        pi::timer::spin_sleep_ms(500);
-        console::kprintln!("test2 {}", i);
+        ulib::sleep(500);
+        ulib::println!("test2 {}", i);
         i += 1;
     }
 }
\ No newline at end of file
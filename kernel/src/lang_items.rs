@@ -24,6 +24,8 @@ pub extern fn panic_fmt(fmt: ::std::fmt::Arguments, file: &'static str, line: u3
     kprintln!("COL: {}", col);
     kprintln!("");
     kprintln!("{}", fmt);
+    kprintln!("");
+    ::backtrace::print_panic(::aarch64::fp());
 
     loop { unsafe { asm!("wfe") } }
 }
@@ -0,0 +1,105 @@
+use std::io;
+use std::sync::Arc;
+
+use mutex::Mutex;
+
+/// The timing of an alarm.
+#[derive(Debug, Default)]
+struct Timing {
+    /// The time of the next expiration, if the alarm is armed.
+    deadline: Option<u64>,
+    /// The period of a periodic alarm, or `0` for a one-shot one.
+    interval: u64,
+    /// The number of expirations not yet read.
+    expirations: u64,
+}
+
+impl Timing {
+    /// Counts the expirations up to `now`, re-arming a periodic alarm for its
+    /// next period and disarming a one-shot one.
+    fn update(&mut self, now: u64) {
+        let deadline = match self.deadline {
+            Some(deadline) if now >= deadline => deadline,
+            _ => return
+        };
+
+        if self.interval == 0 {
+            self.expirations += 1;
+            self.deadline = None;
+        } else {
+            let periods = (now - deadline) / self.interval + 1;
+            self.expirations += periods;
+            self.deadline = Some(deadline + periods * self.interval);
+        }
+    }
+}
+
+/// A one-shot or periodic interval timer, referenced through a descriptor.
+/// Reading the descriptor returns the number of times the alarm expired
+/// since it was last read, blocking until it expires at least once.
+///
+/// Alarms share the timer compare channel with the scheduler tick: the
+/// scheduler arms the timer for the earliest of the end of the time slice
+/// and the next expiration of any alarm.
+///
+/// Cloning an `Alarm` opens another reference to the same alarm.
+#[derive(Debug, Clone)]
+pub struct Alarm(Arc<Mutex<Timing>>);
+
+impl Alarm {
+    /// Returns a new, disarmed alarm.
+    pub fn new() -> Alarm {
+        Alarm(Arc::new(Mutex::new(Timing::default())))
+    }
+
+    /// Arms the alarm to expire `initial` microseconds after `now` and then,
+    /// if `interval` is not `0`, every `interval` microseconds. An `initial`
+    /// of `0` disarms it. Expirations that have not been read are discarded.
+    pub fn set(&self, now: u64, initial: u64, interval: u64) {
+        let mut timing = self.0.lock();
+        timing.deadline = if initial == 0 { None } else { Some(now + initial) };
+        timing.interval = interval;
+        timing.expirations = 0;
+    }
+
+    /// Returns the time of the next expiration after `now`, if the alarm is
+    /// armed. Expirations up to `now` are counted, so that an alarm nobody
+    /// reads does not keep a deadline in the past.
+    pub fn deadline(&self, now: u64) -> Option<u64> {
+        let mut timing = self.0.lock();
+        timing.update(now);
+        timing.deadline
+    }
+
+    /// Returns the number of times the alarm expired up to `now` since the
+    /// last call, resetting the count.
+    pub fn take_expirations(&self, now: u64) -> u64 {
+        let mut timing = self.0.lock();
+        timing.update(now);
+        ::std::mem::replace(&mut timing.expirations, 0)
+    }
+
+    /// Reads the number of expirations up to `now` into `buf` as a
+    /// little-endian `u64`. This method never blocks.
+    ///
+    /// # Errors
+    ///
+    /// If `buf` is shorter than 8 bytes, returns an error of kind
+    /// `InvalidInput`. If the alarm has not expired, returns an error of kind
+    /// `WouldBlock`; the caller should retry later.
+    pub fn read(&self, now: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.len() < 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "buffer too small"));
+        }
+
+        match self.take_expirations(now) {
+            0 => Err(io::Error::new(io::ErrorKind::WouldBlock, "not expired")),
+            count => {
+                for i in 0..8 {
+                    buf[i] = (count >> (8 * i)) as u8;
+                }
+                Ok(8)
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::io;
+use std::io::Write;
+
+use console::CONSOLE;
+use pi::timer;
+
+use process::{Alarm, PipeReader, PipeWriter};
+
+/// Type alias for the type of a file descriptor number.
+pub type Fd = usize;
+
+/// A kernel object referenced by a process through a file descriptor.
+///
+/// Cloning a `Descriptor` opens another reference to the same object, like
+/// `dup` would.
+#[derive(Debug, Clone)]
+pub enum Descriptor {
+    /// The kernel console.
+    Console,
+    /// The reading end of a pipe.
+    PipeReader(PipeReader),
+    /// The writing end of a pipe.
+    PipeWriter(PipeWriter),
+    /// An interval timer.
+    Alarm(Alarm),
+}
+
+impl Descriptor {
+    /// Reads from the object referenced by this descriptor into `buf` without
+    /// blocking. An error of kind `WouldBlock` means the caller should retry
+    /// later.
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Descriptor::Console => CONSOLE.lock().try_read(buf),
+            Descriptor::PipeReader(ref reader) => reader.read(buf),
+            Descriptor::Alarm(ref alarm) => alarm.read(timer::current_time(), buf),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not readable"))
+        }
+    }
+
+    /// Writes `buf` to the object referenced by this descriptor without
+    /// blocking. An error of kind `WouldBlock` means the caller should retry
+    /// later.
+    pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Descriptor::Console => CONSOLE.lock().write(buf),
+            Descriptor::PipeWriter(ref writer) => writer.write(buf),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not writable"))
+        }
+    }
+}
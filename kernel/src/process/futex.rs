@@ -0,0 +1,98 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use mutex::Mutex;
+
+/// A process blocked on a futex. The wait ends when the waiter is woken by
+/// `Futexes::wake()` or dropped, e.g. because the wait timed out or the
+/// process was killed; a dropped waiter is never counted as woken.
+#[derive(Debug)]
+pub struct Waiter(Arc<AtomicBool>);
+
+impl Waiter {
+    /// Returns `true` if this waiter has been woken.
+    pub fn woken(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The wait queues of all futexes, keyed by address. A queue only exists
+/// while some process waits on its address.
+#[derive(Debug)]
+pub struct Futexes {
+    queues: BTreeMap<usize, VecDeque<Weak<AtomicBool>>>,
+}
+
+impl Futexes {
+    /// Returns an empty set of wait queues.
+    pub fn new() -> Futexes {
+        Futexes { queues: BTreeMap::new() }
+    }
+
+    /// Appends a new waiter to the queue of the futex at `addr`.
+    pub fn wait(&mut self, addr: usize) -> Waiter {
+        let flag = Arc::new(AtomicBool::new(false));
+        self.queues.entry(addr).or_insert_with(VecDeque::new).push_back(Arc::downgrade(&flag));
+        Waiter(flag)
+    }
+
+    /// Wakes up to `n` waiters of the futex at `addr`, in the order they
+    /// started waiting. Returns the number of waiters woken.
+    pub fn wake(&mut self, addr: usize, n: usize) -> usize {
+        let mut woken = 0;
+        let empty = match self.queues.get_mut(&addr) {
+            Some(queue) => {
+                while woken < n {
+                    match queue.pop_front() {
+                        Some(waiter) => if let Some(flag) = waiter.upgrade() {
+                            flag.store(true, Ordering::SeqCst);
+                            woken += 1;
+                        },
+                        None => break
+                    }
+                }
+
+                // Forget waiters that are gone
+                queue.retain(|w| w.upgrade().is_some());
+                queue.is_empty()
+            },
+            None => false
+        };
+
+        if empty {
+            self.queues.remove(&addr);
+        }
+
+        woken
+    }
+
+    /// Returns the number of live waiters of the futex at `addr`.
+    pub fn waiters(&self, addr: usize) -> usize {
+        self.queues.get(&addr)
+            .map(|q| q.iter().filter(|w| w.upgrade().is_some()).count())
+            .unwrap_or(0)
+    }
+}
+
+/// The futexes of all processes. Addresses are physical, so one table is
+/// shared by every process.
+pub struct GlobalFutexes(Mutex<Option<Futexes>>);
+
+impl GlobalFutexes {
+    /// Returns an empty table; the queues are allocated on first use.
+    pub const fn uninitialized() -> GlobalFutexes {
+        GlobalFutexes(Mutex::new(None))
+    }
+
+    /// Locks the table, allocating it if needed, and calls `f` with it.
+    pub fn with<F, R>(&self, f: F) -> R
+        where F: FnOnce(&mut Futexes) -> R
+    {
+        let mut guard = self.0.lock();
+        if guard.is_none() {
+            *guard = Some(Futexes::new());
+        }
+        f(guard.as_mut().unwrap())
+    }
+}
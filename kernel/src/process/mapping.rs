@@ -0,0 +1,77 @@
+use std::fmt;
+use std::ptr::Unique;
+
+use alloc::allocator::{Alloc, Layout};
+use process::stack::allocator;
+
+/// An anonymous memory mapping of a process, created with the `mmap` system
+/// call. The memory is zeroed when mapped and freed when the mapping is
+/// dropped: when the process unmaps it or terminates.
+pub struct Mapping {
+    ptr: Unique<u8>,
+    size: usize,
+}
+
+impl Mapping {
+    /// Mappings are made of whole pages.
+    pub const PAGE_SIZE: usize = 4096;
+
+    /// The largest mapping a process may create at once.
+    pub const MAX_SIZE: usize = 64 << 20;
+
+    /// The layout of a mapping of `size` bytes.
+    fn layout(size: usize) -> Layout {
+        unsafe { Layout::from_size_align_unchecked(size, Self::PAGE_SIZE) }
+    }
+
+    /// Returns a new zeroed mapping of at least `len` bytes, rounded up to a
+    /// whole number of pages. Returns `None` if `len` is `0` or larger than
+    /// `MAX_SIZE`, or if there is not enough memory.
+    pub fn new(len: usize) -> Option<Mapping> {
+        if len == 0 || len > Self::MAX_SIZE {
+            return None;
+        }
+
+        let size = (len + Self::PAGE_SIZE - 1) & !(Self::PAGE_SIZE - 1);
+        let raw_ptr = unsafe {
+            let raw_ptr: *mut u8 = allocator().alloc(Self::layout(size)).ok()?;
+            raw_ptr.write_bytes(0, size);
+            raw_ptr
+        };
+
+        let ptr = Unique::new(raw_ptr).expect("non-null");
+        Some(Mapping { ptr, size })
+    }
+
+    /// The address of the first byte of the mapping.
+    pub fn start(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
+
+    /// The size of the mapping in bytes, a multiple of `PAGE_SIZE`.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if `[addr, addr + len)` lies within this mapping.
+    pub fn contains(&self, addr: usize, len: usize) -> bool {
+        addr >= self.start() && len <= self.size && addr - self.start() <= self.size - len
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            allocator().dealloc(self.ptr.as_ptr(), Self::layout(self.size))
+        }
+    }
+}
+
+impl fmt::Debug for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mapping")
+            .field("start", &self.start())
+            .field("size", &self.size)
+            .finish()
+    }
+}
@@ -2,8 +2,23 @@ mod process;
 mod state;
 mod scheduler;
 mod stack;
+mod descriptor;
+mod realtime;
+mod mapping;
+mod futex;
+mod alarm;
+mod pipe;
+
+#[cfg(test)]
+mod tests;
 
 pub use self::process::{Process, Id};
-pub use self::state::State;
-pub use self::scheduler::{GlobalScheduler, TICK};
+pub use self::state::{State, ExitStatus};
+pub use self::scheduler::{GlobalScheduler, Hardware, Policy, TICK};
 pub use self::stack::Stack;
+pub use self::descriptor::{Descriptor, Fd};
+pub use self::realtime::{RealTime, RealTimeError};
+pub use self::mapping::Mapping;
+pub use self::futex::{Futexes, GlobalFutexes, Waiter};
+pub use self::alarm::Alarm;
+pub use self::pipe::{pipe, PipeReader, PipeWriter, PIPE_CAPACITY};
@@ -0,0 +1,171 @@
+use std::io;
+use std::fmt;
+use std::sync::Arc;
+
+use mutex::Mutex;
+
+/// The capacity, in bytes, of the ring buffer backing a pipe.
+pub const PIPE_CAPACITY: usize = 4096;
+
+/// The shared state of a pipe: a bounded ring buffer plus the number of
+/// reader and writer ends that are still open.
+struct Buffer {
+    data: Box<[u8]>,
+    head: usize,
+    len: usize,
+    readers: usize,
+    writers: usize,
+}
+
+impl Buffer {
+    fn new() -> Buffer {
+        Buffer {
+            data: vec![0u8; PIPE_CAPACITY].into_boxed_slice(),
+            head: 0,
+            len: 0,
+            readers: 0,
+            writers: 0
+        }
+    }
+
+    /// Moves as many bytes as possible from the buffer into `buf`.
+    fn pop(&mut self, buf: &mut [u8]) -> usize {
+        let count = ::std::cmp::min(buf.len(), self.len);
+        for i in 0..count {
+            buf[i] = self.data[(self.head + i) % PIPE_CAPACITY];
+        }
+        self.head = (self.head + count) % PIPE_CAPACITY;
+        self.len -= count;
+        count
+    }
+
+    /// Moves as many bytes as possible from `buf` into the buffer.
+    fn push(&mut self, buf: &[u8]) -> usize {
+        let count = ::std::cmp::min(buf.len(), PIPE_CAPACITY - self.len);
+        let tail = self.head + self.len;
+        for i in 0..count {
+            self.data[(tail + i) % PIPE_CAPACITY] = buf[i];
+        }
+        self.len += count;
+        count
+    }
+}
+
+/// A unidirectional byte channel between processes.
+///
+/// A pipe is only ever accessed through its ends, `PipeReader` and
+/// `PipeWriter`, which are created in pairs by `pipe()`.
+struct Pipe(Mutex<Buffer>);
+
+/// Creates a new pipe, returning its reader and writer ends.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let pipe = Arc::new(Pipe(Mutex::new(Buffer::new())));
+    (PipeReader::new(pipe.clone()), PipeWriter::new(pipe))
+}
+
+/// The reading end of a pipe.
+///
+/// Cloning a `PipeReader` opens another reading end of the same pipe.
+pub struct PipeReader(Arc<Pipe>);
+
+impl PipeReader {
+    fn new(pipe: Arc<Pipe>) -> PipeReader {
+        (pipe.0).lock().readers += 1;
+        PipeReader(pipe)
+    }
+
+    /// Reads bytes from the pipe into `buf`. This method never blocks.
+    ///
+    /// Returns the number of bytes read. `Ok(0)` is returned once the pipe is
+    /// empty and every writer has been closed (EOF).
+    ///
+    /// # Errors
+    ///
+    /// If the pipe is empty but writers are still open, returns an error of
+    /// kind `WouldBlock`; the caller should retry later.
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = (self.0).0.lock();
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match inner.pop(buf) {
+            0 if inner.writers > 0 => Err(io::Error::new(io::ErrorKind::WouldBlock, "pipe empty")),
+            read => Ok(read)
+        }
+    }
+}
+
+impl Clone for PipeReader {
+    fn clone(&self) -> PipeReader {
+        PipeReader::new(self.0.clone())
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        (self.0).0.lock().readers -= 1;
+    }
+}
+
+/// The writing end of a pipe.
+///
+/// Cloning a `PipeWriter` opens another writing end of the same pipe.
+pub struct PipeWriter(Arc<Pipe>);
+
+impl PipeWriter {
+    fn new(pipe: Arc<Pipe>) -> PipeWriter {
+        (pipe.0).lock().writers += 1;
+        PipeWriter(pipe)
+    }
+
+    /// Writes bytes from `buf` into the pipe. This method never blocks.
+    ///
+    /// Returns the number of bytes written, which may be less than
+    /// `buf.len()` if the pipe does not have enough free space.
+    ///
+    /// # Errors
+    ///
+    /// If every reader has been closed, returns an error of kind
+    /// `BrokenPipe`. If the pipe is full, returns an error of kind
+    /// `WouldBlock`; the caller should retry later.
+    pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = (self.0).0.lock();
+        if inner.readers == 0 {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "no readers"));
+        }
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match inner.push(buf) {
+            0 => Err(io::Error::new(io::ErrorKind::WouldBlock, "pipe full")),
+            written => Ok(written)
+        }
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> PipeWriter {
+        PipeWriter::new(self.0.clone())
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        (self.0).0.lock().writers -= 1;
+    }
+}
+
+impl fmt::Debug for PipeReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PipeReader")
+    }
+}
+
+impl fmt::Debug for PipeWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PipeWriter")
+    }
+}
@@ -1,12 +1,19 @@
 use traps::TrapFrame;
-use process::{State, Stack};
+use process::{State, ExitStatus, Stack, Descriptor, Fd, RealTime, Mapping};
 use process::state::EventPollFn;
 use std::mem;
+use ulib;
 
 fn process_state_poll_nop(_process: &mut Process) -> bool {
     false
 }
 
+/// The address user processes return to when their entry function returns.
+/// Terminates the process with exit code `0`.
+extern "C" fn process_exit() {
+    ulib::exit(0);
+}
+
 /// Type alias for the type of a process ID.
 pub type Id = u64;
 
@@ -19,6 +26,22 @@ pub struct Process {
     pub stack: Stack,
     /// The scheduling state of the process.
     pub state: State,
+    /// The open file descriptors of the process, indexed by `Fd`.
+    pub descriptors: Vec<Option<Descriptor>>,
+    /// The ID of the process that created this one, if any. Only processes
+    /// with a parent are kept around as zombies after they terminate.
+    pub parent: Option<Id>,
+    /// The real-time parameters of the process. Processes without them are
+    /// scheduled best-effort, after all runnable real-time processes.
+    pub realtime: Option<RealTime>,
+    /// The time, in nanoseconds, the process has spent running as a
+    /// best-effort process, used to pick the next one under `Policy::Fair`.
+    pub vruntime: u64,
+    /// The anonymous memory mappings of the process, its heap. They are freed
+    /// when the process terminates.
+    pub mappings: Vec<Mapping>,
+    /// Whether the system calls of the process are logged to the console.
+    pub traced: bool,
 }
 
 impl Process {
@@ -40,12 +63,20 @@ impl Process {
                         general_registers: [0; 32]
                     }),
                     stack,
-                    state: State::Ready
+                    state: State::Ready,
+                    descriptors: Vec::new(),
+                    parent: None,
+                    realtime: None,
+                    vruntime: 0,
+                    mappings: Vec::new(),
+                    traced: false
                 }
             })
     }
 
     // Create process with a given entry point address
+    // The process exits when the entry function returns
+    // Descriptors 0, 1 and 2 (stdin, stdout, stderr) refer to the console
     pub fn create_process(entry: *const ()) -> Option<Process> {
         Self::new()
             .map(|mut process| {
@@ -54,10 +85,88 @@ impl Process {
                 };
                 process.trap_frame.stack_pointer = sp;
                 process.trap_frame.program_counter = entry as u64;
+                process.trap_frame.set_lr(process_exit as u64);
+                // Standard input, output and error all refer to the console
+                for _ in 0..3 {
+                    process.add_descriptor(Descriptor::Console);
+                }
                 process
             })
     }
 
+    /// Terminates this process with `status`, closing all of its descriptors
+    /// and freeing its memory mappings. The process becomes a zombie until it
+    /// is reaped.
+    pub fn exit(&mut self, status: ExitStatus) {
+        self.descriptors.clear();
+        self.mappings.clear();
+        self.state = State::Zombie(status);
+    }
+
+    /// Returns `true` if this process has terminated.
+    pub fn is_zombie(&self) -> bool {
+        match self.state {
+            State::Zombie(_) => true,
+            _ => false
+        }
+    }
+
+    /// Installs `descriptor` in the lowest free slot of this process's
+    /// descriptor table and returns its file descriptor number.
+    pub fn add_descriptor(&mut self, descriptor: Descriptor) -> Fd {
+        match self.descriptors.iter().position(|d| d.is_none()) {
+            Some(fd) => {
+                self.descriptors[fd] = Some(descriptor);
+                fd
+            },
+            None => {
+                self.descriptors.push(Some(descriptor));
+                self.descriptors.len() - 1
+            }
+        }
+    }
+
+    /// Returns the descriptor referenced by `fd`, if it is open.
+    pub fn descriptor(&self, fd: Fd) -> Option<&Descriptor> {
+        self.descriptors.get(fd).and_then(|d| d.as_ref())
+    }
+
+    /// Closes `fd`. Returns the descriptor that was closed, or `None` if `fd`
+    /// was not open.
+    pub fn close_descriptor(&mut self, fd: Fd) -> Option<Descriptor> {
+        self.descriptors.get_mut(fd).and_then(|d| d.take())
+    }
+
+    /// Maps at least `len` bytes of zeroed memory into this process. Returns
+    /// the start address of the new mapping, or `None` if it could not be
+    /// created.
+    pub fn map(&mut self, len: usize) -> Option<usize> {
+        let mapping = Mapping::new(len)?;
+        let start = mapping.start();
+        self.mappings.push(mapping);
+        Some(start)
+    }
+
+    /// Unmaps the mapping starting at `addr`, freeing its memory. Returns
+    /// `false` if no mapping of this process starts at `addr`.
+    pub fn unmap(&mut self, addr: usize) -> bool {
+        match self.mappings.iter().position(|m| m.start() == addr) {
+            Some(i) => {
+                self.mappings.swap_remove(i);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Returns `true` if this process may access the `len` bytes at `addr`:
+    /// if they lie within its stack or one of its memory mappings.
+    pub fn owns(&self, addr: usize, len: usize) -> bool {
+        let bottom = self.stack.bottom().as_u64() as usize;
+        let in_stack = addr >= bottom && len <= Stack::SIZE && addr - bottom <= Stack::SIZE - len;
+        in_stack || self.mappings.iter().any(|m| m.contains(addr, len))
+    }
+
     /// Returns `true` if this process is ready to be scheduled.
     ///
     /// This functions returns `true` only if one of the following holds:
@@ -77,13 +186,17 @@ impl Process {
         match self.state {
             State::Ready => return true,
             State::Running => return false,
+            State::Stopped(_) | State::Zombie(_) => return false,
             State::Waiting(ref mut f) => {
                 mem::swap(f, &mut poll_fn);
             }
         }
 
         let ret = poll_fn(self);
-        if let State::Waiting(ref mut f) = self.state {
+        if ret {
+            // The event has arrived; don't poll for it again
+            self.state = State::Ready;
+        } else if let State::Waiting(ref mut f) = self.state {
             mem::swap(f, &mut poll_fn);
         }
         return ret;
@@ -0,0 +1,83 @@
+use std::cmp::min;
+
+/// Utilisation is expressed in parts per million of the CPU.
+pub const FULL_UTILIZATION: u64 = 1000 * 1000;
+
+/// The reasons `Scheduler::set_realtime()` can refuse to change the
+/// scheduling class of a process.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RealTimeError {
+    /// There is no such process.
+    NoProcess,
+    /// The budget is `0` or larger than the period.
+    Invalid,
+    /// Admitting the process would take the total utilisation of real-time
+    /// processes above 100%.
+    Rejected,
+}
+
+/// Real-time scheduling parameters and budget accounting of a process. A
+/// real-time process may run for `budget` microseconds in every `period`
+/// microseconds; the end of the current period is its deadline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RealTime {
+    period: u64,
+    budget: u64,
+    deadline: u64,
+    remaining: u64,
+}
+
+impl RealTime {
+    /// Returns real-time parameters with a first period starting at `now`.
+    /// Returns `None` if `budget` is zero or larger than `period`.
+    pub fn new(period: u64, budget: u64, now: u64) -> Option<RealTime> {
+        if budget == 0 || budget > period {
+            return None;
+        }
+
+        Some(RealTime {
+            period,
+            budget,
+            deadline: now + period,
+            remaining: budget
+        })
+    }
+
+    /// The fraction of the CPU this process may use, in parts per million.
+    pub fn utilization(&self) -> u64 {
+        self.budget * FULL_UTILIZATION / self.period
+    }
+
+    /// The absolute deadline of the current period.
+    pub fn deadline(&self) -> u64 {
+        self.deadline
+    }
+
+    /// The budget left in the current period.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Returns `true` if the budget of the current period is used up. The
+    /// process must not run again until the next period starts.
+    pub fn throttled(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Starts a new period, refilling the budget, if the deadline of the
+    /// current one has passed at `now`. Periods missed entirely are skipped.
+    pub fn replenish(&mut self, now: u64) {
+        if now < self.deadline {
+            return;
+        }
+
+        let missed = (now - self.deadline) / self.period;
+        self.deadline += (missed + 1) * self.period;
+        self.remaining = self.budget;
+    }
+
+    /// Charges `us` microseconds of CPU time against the current budget.
+    pub fn charge(&mut self, us: u64) {
+        self.remaining -= min(us, self.remaining);
+    }
+}
@@ -1,22 +1,129 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::cmp::{min, max};
 use std::mem;
 
 use aarch64;
-use console;
+use console::{self, CONSOLE, Signal};
+use gdb;
 use mutex::Mutex;
-use process::{Process, State, Id};
-use traps::TrapFrame;
-use start_shell;
+use process::{Process, State, ExitStatus, Id, RealTime, RealTimeError, Descriptor};
+use process::realtime::FULL_UTILIZATION;
+use traps::{self, TrapFrame};
+use {start_shell, SCHEDULER};
 
-use pi::{timer, interrupt};
+use pi::timer;
+use pi::interrupt::Interrupt;
+use pi::atags::Atags;
 
 /// The `tick` time.
 // FIXME: When you're ready, change this to something more reasonable.
 pub const TICK: u32 = 2 * 1000 * 1000;
 
+/// The shortest time slice, in microseconds, the timer is programmed for.
+/// Shorter matches could be missed by the time the timer is armed.
+const MIN_TIME_SLICE: u64 = 100;
+
+/// The period, in microseconds, in which every ready process should get to
+/// run once under `Policy::Fair`.
+const FAIR_LATENCY: u64 = 20 * 1000;
+
+/// The shortest time slice, in microseconds, given under `Policy::Fair`.
+const FAIR_MIN_GRANULARITY: u64 = 2 * 1000;
+
+/// How far, in nanoseconds, behind the smallest virtual runtime a process that
+/// wakes up is placed. This lets processes that block often (like the shell
+/// waiting for input) run first, without starving everybody else.
+const SLEEPER_CREDIT: u64 = FAIR_LATENCY * 1000 / 2;
+
+/// The policy used to schedule best-effort processes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// Processes take turns in queue order, each running for a `TICK`.
+    RoundRobin,
+    /// Completely fair: the process with the smallest virtual runtime runs
+    /// next, for its share of `FAIR_LATENCY`.
+    Fair,
+}
+
+impl Policy {
+    /// Parses the `sched=rr` or `sched=fair` option out of a kernel command
+    /// line. Returns `None` if there is no such option.
+    pub fn from_cmdline(cmdline: &str) -> Option<Policy> {
+        for arg in cmdline.split(' ') {
+            match arg {
+                "sched=rr" => return Some(Policy::RoundRobin),
+                "sched=fair" | "sched=cfs" => return Some(Policy::Fair),
+                _ => ()
+            }
+        }
+
+        None
+    }
+
+    /// Returns the policy selected on the kernel command line passed in by
+    /// the firmware (`cmdline.txt`). Defaults to `Policy::RoundRobin`.
+    fn from_boot_args() -> Policy {
+        Atags::get()
+            .filter_map(|atag| atag.cmd())
+            .filter_map(Policy::from_cmdline)
+            .next()
+            .unwrap_or(Policy::RoundRobin)
+    }
+}
+
+/// The hardware services the scheduler depends on. Abstracted so that the
+/// scheduling logic can be driven by a mock under `cargo test`.
+pub trait Hardware {
+    /// Returns the current time in microseconds.
+    fn now(&self) -> u64;
+
+    /// Idles the CPU until an interrupt arrives.
+    fn idle(&self);
+
+    /// Returns `true` if the current process may use FP/SIMD registers, in
+    /// which case they are saved in its trap frame on every exception.
+    fn user_fp(&self) -> bool;
+
+    /// Enables or disables FP/SIMD access for the current process. While it
+    /// is disabled, the first use traps so that the registers can be loaded.
+    fn set_user_fp(&self, enabled: bool);
+
+    /// Enables software stepping if the process about to be returned to with
+    /// the saved program status `spsr` is being stepped, and disables it
+    /// otherwise.
+    fn restore_step(&self, spsr: u64);
+}
+
+/// The hardware of the Raspberry Pi: the ARM system timer, `wfi`,
+/// `CPACR_EL1` and `MDSCR_EL1`.
+#[derive(Debug)]
+pub struct Pi;
+
+impl Hardware for Pi {
+    fn now(&self) -> u64 {
+        timer::current_time()
+    }
+
+    fn idle(&self) {
+        aarch64::wait_for_interrupt();
+    }
+
+    fn user_fp(&self) -> bool {
+        unsafe { aarch64::user_fp_enabled() }
+    }
+
+    fn set_user_fp(&self, enabled: bool) {
+        unsafe { aarch64::set_user_fp(enabled) }
+    }
+
+    fn restore_step(&self, spsr: u64) {
+        unsafe { aarch64::debug::restore_step(spsr) }
+    }
+}
+
 /// Process scheduler for the entire machine.
 #[derive(Debug)]
-pub struct GlobalScheduler(Mutex<Option<Scheduler>>);
+pub struct GlobalScheduler(Mutex<Option<Scheduler<Pi>>>);
 
 impl GlobalScheduler {
     /// Returns an uninitialized wrapper around a local scheduler.
@@ -34,17 +141,111 @@ impl GlobalScheduler {
     /// process to `new_state`, saving `tf` into the current process, and
     /// restoring the next process's trap frame into `tf`. For more details, see
     /// the documentation on `Scheduler::switch()`.
+    ///
+    /// The timer is then armed for the time slice of the new process.
     #[must_use]
     pub fn switch(&self, new_state: State, tf: &mut TrapFrame) -> Option<Id> {
-        self.0.lock().as_mut().expect("scheduler uninitialized").switch(new_state, tf)
+        let mut guard = self.0.lock();
+        let scheduler = guard.as_mut().expect("scheduler uninitialized");
+        let id = scheduler.switch(new_state, tf);
+        timer::tick_in(scheduler.time_slice() as u32);
+        id
+    }
+
+    /// Makes the current process a real-time process, or a best-effort one if
+    /// `period` is `0`. For more details, see the documentation on
+    /// `Scheduler::set_realtime()`.
+    pub fn set_realtime(&self, period: u64, budget: u64) -> Result<(), RealTimeError> {
+        let mut guard = self.0.lock();
+        let scheduler = guard.as_mut().expect("scheduler uninitialized");
+        match scheduler.current {
+            Some(id) => scheduler.set_realtime(id, period, budget),
+            None => Err(RealTimeError::NoProcess)
+        }
+    }
+
+    /// Calls `f` with a mutable reference to the currently running process
+    /// and returns its result, or returns `None` if no process is running.
+    pub fn with_current<F, R>(&self, f: F) -> Option<R>
+        where F: FnOnce(&mut Process) -> R
+    {
+        self.0.lock().as_mut().expect("scheduler uninitialized").current_mut().map(f)
+    }
+
+    /// Calls `f` with a mutable reference to the process `id` and returns its
+    /// result, or returns `None` if there is no such process.
+    pub fn with_process<F, R>(&self, id: Id, f: F) -> Option<R>
+        where F: FnOnce(&mut Process) -> R
+    {
+        self.0.lock().as_mut().expect("scheduler uninitialized").process_mut(id).map(f)
+    }
+
+    /// Returns the total time, in microseconds, spent idling in `switch()`
+    /// while no process was ready, or `0` if the scheduler is uninitialized.
+    pub fn idle_time(&self) -> u64 {
+        self.0.lock().as_ref().map_or(0, |scheduler| scheduler.idle_time())
+    }
+
+    /// Returns the ID of the currently running process, if any.
+    pub fn current(&self) -> Option<Id> {
+        self.0.lock().as_ref().expect("scheduler uninitialized").current
+    }
+
+    /// Kills the process `id`. Returns `false` if there is no such process or
+    /// if it is the currently running one. For more details, see the
+    /// documentation on `Scheduler::kill()`.
+    pub fn kill(&self, id: Id) -> bool {
+        self.0.lock().as_mut().expect("scheduler uninitialized").kill(id)
+    }
+
+    /// Stops the process `id` until it is resumed. Returns `false` if there is
+    /// no such process or if it is the currently running one.
+    pub fn stop(&self, id: Id) -> bool {
+        self.0.lock().as_mut().expect("scheduler uninitialized").stop(id)
+    }
+
+    /// Delivers the job control request `signal` to the process `id`, whose
+    /// state is in `tf` if it is running. For more details, see the
+    /// documentation on `Scheduler::signal()`.
+    ///
+    /// If the process was switched out, the timer is armed for the time slice
+    /// of the next one.
+    pub fn signal(&self, id: Id, signal: Signal, tf: &mut TrapFrame) -> bool {
+        let mut guard = self.0.lock();
+        let scheduler = guard.as_mut().expect("scheduler uninitialized");
+        let running = scheduler.current == Some(id);
+        let delivered = scheduler.signal(id, signal, tf);
+        if running {
+            timer::tick_in(scheduler.time_slice() as u32);
+        }
+        delivered
+    }
+
+    /// Resumes the stopped process `id`. Returns `false` if there is no such
+    /// stopped process.
+    pub fn resume(&self, id: Id) -> bool {
+        self.0.lock().as_mut().expect("scheduler uninitialized").resume(id)
+    }
+
+    /// Returns `true` if the process `id` exists and is stopped.
+    pub fn is_stopped(&self, id: Id) -> bool {
+        self.0.lock().as_mut().expect("scheduler uninitialized").is_stopped(id)
+    }
+
+    /// Removes the terminated process `id` from the scheduler and returns its
+    /// exit status. Returns `None` if `id` does not exist or is still alive.
+    pub fn reap(&self, id: Id) -> Option<ExitStatus> {
+        self.0.lock().as_mut().expect("scheduler uninitialized").reap(id)
     }
 
     /// Initializes the scheduler and starts executing processes in user space
     /// using timer interrupt based preemptive scheduling. This method should
     /// not return under normal conditions.
     pub fn start(&self) {
-        *self.0.lock() = Some(Scheduler::new());
-        interrupt::Controller::new().enable(interrupt::Interrupt::Timer1);
+        *self.0.lock() = Some(Scheduler::new(Pi, Policy::from_boot_args()));
+        traps::register_irq_handler(Interrupt::Timer1, timer_interrupt);
+        CONSOLE.lock().enable_receive_interrupt();
+        traps::register_irq_handler(Interrupt::Aux, console_interrupt);
         timer::tick_in(TICK);
 
         // Bootstrap the first process (init process)
@@ -71,20 +272,77 @@ impl GlobalScheduler {
     }
 }
 
+/// Handles the timer interrupt that ends a time slice. The switch re-arms the
+/// timer for the end of the next time slice or the next alarm, whichever
+/// comes first.
+fn timer_interrupt(_: Interrupt, tf: &mut TrapFrame) {
+    SCHEDULER.switch(State::Ready, tf).expect("Fatal: no process running");
+}
+
+/// Handles the interrupt of the console UART receiving input: the input is
+/// buffered, and Ctrl-C or Ctrl-Z is delivered to the foreground process
+/// right away, even if it is the one running. During a GDB session, Ctrl-C
+/// stops whatever runs in the debugger instead.
+fn console_interrupt(_: Interrupt, tf: &mut TrapFrame) {
+    let (signal, foreground) = {
+        let mut console = CONSOLE.lock();
+        (console.take_signal(), console.foreground())
+    };
+
+    match (signal, foreground) {
+        (Some(Signal::Interrupt), _) if gdb::attached() => traps::break_in(tf),
+        (Some(signal), Some(id)) => {
+            SCHEDULER.signal(id, signal, tf);
+        },
+        _ => ()
+    }
+}
+
+/// A scheduler with two classes: real-time processes are scheduled earliest
+/// deadline first, ahead of best-effort processes, which are scheduled
+/// according to a `Policy`.
 #[derive(Debug)]
-struct Scheduler {
-    processes: VecDeque<Process>,
+pub(super) struct Scheduler<H: Hardware> {
+    hardware: H,
+    policy: Policy,
+    processes: BTreeMap<Id, Process>,
+    /// Round-robin order of the processes. The current one is at the front.
+    queue: VecDeque<Id>,
+    /// Ready best-effort processes ordered by virtual runtime, only used by
+    /// `Policy::Fair`. Entries of processes that are gone, no longer ready or
+    /// whose virtual runtime changed are stale and skipped.
+    timeline: BTreeSet<(u64, Id)>,
+    /// Waiting best-effort processes, only used by `Policy::Fair`. They are
+    /// polled and moved to `timeline` when their event arrives. Entries of
+    /// processes that are gone or no longer waiting are stale and dropped.
+    waiters: BTreeSet<Id>,
+    /// The virtual runtime of the last process picked from `timeline`. It
+    /// only ever grows.
+    min_vruntime: u64,
     current: Option<Id>,
     last_id: Option<Id>,
+    /// The time the current process was switched in.
+    slice_start: u64,
+    /// The total time spent idling in `switch()`.
+    idle_time: u64,
 }
 
-impl Scheduler {
-    /// Returns a new `Scheduler` with an empty queue.
-    fn new() -> Scheduler {
+impl<H: Hardware> Scheduler<H> {
+    /// Returns a new `Scheduler` with an empty queue, running on `hardware`
+    /// and scheduling best-effort processes with `policy`.
+    pub(super) fn new(hardware: H, policy: Policy) -> Scheduler<H> {
         Scheduler {
-            processes: VecDeque::new(),
+            hardware,
+            policy,
+            processes: BTreeMap::new(),
+            queue: VecDeque::new(),
+            timeline: BTreeSet::new(),
+            waiters: BTreeSet::new(),
+            min_vruntime: 0,
             current: None,
-            last_id: Some(0)
+            last_id: Some(0),
+            slice_start: 0,
+            idle_time: 0
         }
     }
 
@@ -96,14 +354,17 @@ impl Scheduler {
     /// If this is the first process added, it is marked as the current process.
     /// It is the caller's responsibility to ensure that the first time `switch`
     /// is called, that process is executing on the CPU.
-    fn add(&mut self, mut process: Process) -> Option<Id> {
+    pub(super) fn add(&mut self, mut process: Process) -> Option<Id> {
         if self.last_id.is_none() {
             return None;
         }
 
         let last_id = self.last_id.unwrap() + 1;
         process.trap_frame.thread_id = last_id;
-        self.processes.push_back(process);
+        // New processes start level with the others
+        process.vruntime = self.min_vruntime;
+        self.processes.insert(last_id, process);
+        self.queue.push_back(last_id);
 
         if last_id == ::std::u64::MAX {
             // TODO: implement wrapping for process IDs
@@ -114,60 +375,464 @@ impl Scheduler {
 
         if self.processes.len() == 1 {
             self.current = Some(last_id);
+        } else {
+            self.enqueue(last_id);
         }
 
         return Some(last_id);
     }
 
+    /// Returns a mutable reference to the currently running process, if any.
+    pub(super) fn current_mut(&mut self) -> Option<&mut Process> {
+        match self.current {
+            Some(id) => self.processes.get_mut(&id),
+            None => None
+        }
+    }
+
+    /// Returns a mutable reference to the process `id`, if it exists.
+    pub(super) fn process_mut(&mut self, id: Id) -> Option<&mut Process> {
+        self.processes.get_mut(&id)
+    }
+
+    /// Puts the process `id` on the timeline if it is a ready best-effort
+    /// process, or with the waiters if it is a waiting one, and the policy is
+    /// `Policy::Fair`.
+    fn enqueue(&mut self, id: Id) {
+        if self.policy != Policy::Fair {
+            return;
+        }
+
+        if let Some(process) = self.processes.get(&id) {
+            match (&process.state, process.realtime) {
+                (&State::Ready, None) => { self.timeline.insert((process.vruntime, id)); },
+                (&State::Waiting(_), None) => { self.waiters.insert(id); },
+                _ => ()
+            }
+        }
+    }
+
+    /// Removes the process `id` from the scheduler entirely.
+    fn remove(&mut self, id: Id) -> Option<Process> {
+        self.queue.retain(|&i| i != id);
+        self.processes.remove(&id)
+    }
+
+    /// Kills the process `id`, closing its descriptors. If the process has a
+    /// parent, it is kept as a zombie until reaped. Otherwise it is removed
+    /// right away. The currently running process cannot be killed this way;
+    /// it should `switch` into `State::Zombie` instead.
+    pub(super) fn kill(&mut self, id: Id) -> bool {
+        if self.current == Some(id) {
+            return false;
+        }
+
+        let orphan = match self.processes.get_mut(&id) {
+            Some(process) => {
+                if process.is_zombie() {
+                    return false;
+                }
+
+                process.exit(ExitStatus::Killed);
+                process.parent.is_none()
+            },
+            None => return false
+        };
+
+        if orphan {
+            self.remove(id);
+        }
+        true
+    }
+
+    pub(super) fn stop(&mut self, id: Id) -> bool {
+        if self.current == Some(id) {
+            return false;
+        }
+
+        match self.processes.get_mut(&id) {
+            Some(process) => {
+                match process.state {
+                    State::Stopped(_) | State::Zombie(_) => return false,
+                    _ => ()
+                }
+
+                let state = mem::replace(&mut process.state, State::Ready);
+                process.state = State::Stopped(Box::new(state));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Delivers the job control request `signal` to the process `id`:
+    /// `Signal::Interrupt` kills it and `Signal::Stop` stops it. If it is the
+    /// current process, its state is saved from `tf` and the next process is
+    /// switched in, like `switch()` does. Returns `false` if there is no such
+    /// process or if it is already terminated or stopped.
+    pub(super) fn signal(&mut self, id: Id, signal: Signal, tf: &mut TrapFrame) -> bool {
+        if self.current != Some(id) {
+            return match signal {
+                Signal::Interrupt => self.kill(id),
+                Signal::Stop => self.stop(id)
+            };
+        }
+
+        let state = match signal {
+            Signal::Interrupt => State::Zombie(ExitStatus::Killed),
+            Signal::Stop => State::Stopped(Box::new(State::Ready))
+        };
+        self.switch(state, tf);
+        true
+    }
+
+    pub(super) fn resume(&mut self, id: Id) -> bool {
+        let resumed = match self.processes.get_mut(&id) {
+            Some(process) => {
+                match mem::replace(&mut process.state, State::Ready) {
+                    State::Stopped(state) => {
+                        process.state = *state;
+                        true
+                    },
+                    state => {
+                        process.state = state;
+                        false
+                    }
+                }
+            },
+            None => false
+        };
+
+        if resumed {
+            self.enqueue(id);
+        }
+        resumed
+    }
+
+    pub(super) fn is_stopped(&self, id: Id) -> bool {
+        match self.processes.get(&id) {
+            Some(process) => match process.state {
+                State::Stopped(_) => true,
+                _ => false
+            },
+            None => false
+        }
+    }
+
+    pub(super) fn reap(&mut self, id: Id) -> Option<ExitStatus> {
+        let status = match self.processes.get(&id) {
+            Some(process) => match process.state {
+                State::Zombie(status) => status,
+                _ => return None
+            },
+            None => return None
+        };
+
+        self.remove(id);
+        Some(status)
+    }
+
+    /// Makes the process `id` a real-time process that may run for `budget`
+    /// microseconds in every `period` microseconds, or a best-effort process
+    /// again if `period` is `0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RealTimeError::NoProcess` if there is no such process,
+    /// `RealTimeError::Invalid` if `budget` is `0` or larger than `period`, and
+    /// `RealTimeError::Rejected` if admitting the process would take the total
+    /// utilisation of real-time processes above 100%.
+    pub(super) fn set_realtime(&mut self, id: Id, period: u64, budget: u64) -> Result<(), RealTimeError> {
+        if !self.processes.contains_key(&id) {
+            return Err(RealTimeError::NoProcess);
+        }
+
+        if period == 0 {
+            self.processes.get_mut(&id).unwrap().realtime = None;
+            self.enqueue(id);
+            return Ok(());
+        }
+
+        let realtime = match RealTime::new(period, budget, self.hardware.now()) {
+            Some(realtime) => realtime,
+            None => return Err(RealTimeError::Invalid)
+        };
+
+        // Admission control
+        let utilization: u64 = self.processes.iter()
+            .filter(|&(&j, _)| j != id)
+            .filter_map(|(_, p)| p.realtime.map(|rt| rt.utilization()))
+            .sum();
+        if utilization + realtime.utilization() > FULL_UTILIZATION {
+            return Err(RealTimeError::Rejected);
+        }
+
+        self.processes.get_mut(&id).unwrap().realtime = Some(realtime);
+        Ok(())
+    }
+
+    /// Returns the ID of the next process to run: the runnable real-time
+    /// process with the earliest deadline or, if there is none, the
+    /// best-effort process chosen by the policy. Real-time processes that
+    /// used up their budget are not runnable until their next period.
+    fn pick_next(&mut self) -> Option<Id> {
+        let now = self.hardware.now();
+        let mut earliest: Option<(u64, Id)> = None;
+        for (&id, process) in self.processes.iter_mut() {
+            let deadline = match process.realtime {
+                Some(ref mut rt) => {
+                    rt.replenish(now);
+                    if rt.throttled() {
+                        continue;
+                    }
+                    rt.deadline()
+                },
+                None => continue
+            };
+
+            if earliest.map_or(true, |(d, _)| deadline < d) && process.is_ready() {
+                earliest = Some((deadline, id));
+            }
+        }
+
+        if let Some((_, id)) = earliest {
+            return Some(id);
+        }
+
+        match self.policy {
+            Policy::RoundRobin => self.pick_round_robin(),
+            Policy::Fair => self.pick_fair()
+        }
+    }
+
+    /// Returns the first ready best-effort process in queue order.
+    fn pick_round_robin(&mut self) -> Option<Id> {
+        for &id in self.queue.iter() {
+            let process = self.processes.get_mut(&id).expect("queued process");
+            if process.realtime.is_none() && process.is_ready() {
+                return Some(id);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the ready best-effort process with the smallest virtual
+    /// runtime. Waiters whose event has arrived are put on the timeline
+    /// first, no further than `SLEEPER_CREDIT` behind.
+    fn pick_fair(&mut self) -> Option<Id> {
+        let floor = self.min_vruntime.saturating_sub(SLEEPER_CREDIT);
+        let waiters: Vec<Id> = self.waiters.iter().cloned().collect();
+        for id in waiters {
+            let woken = match self.processes.get_mut(&id) {
+                Some(process) => {
+                    let waiting = match process.state {
+                        State::Waiting(_) => true,
+                        _ => false
+                    };
+
+                    if !waiting || process.realtime.is_some() {
+                        None
+                    } else if process.is_ready() {
+                        process.vruntime = max(process.vruntime, floor);
+                        Some(process.vruntime)
+                    } else {
+                        continue;
+                    }
+                },
+                None => None
+            };
+
+            self.waiters.remove(&id);
+            if let Some(vruntime) = woken {
+                self.timeline.insert((vruntime, id));
+            }
+        }
+
+        loop {
+            let next = self.timeline.iter().next().cloned();
+            let (vruntime, id) = match next {
+                Some(entry) => entry,
+                None => return None
+            };
+            self.timeline.remove(&(vruntime, id));
+
+            let valid = match self.processes.get(&id) {
+                Some(process) => match (&process.state, process.realtime) {
+                    (&State::Ready, None) => process.vruntime == vruntime,
+                    _ => false
+                },
+                None => false
+            };
+
+            if valid {
+                self.min_vruntime = max(self.min_vruntime, vruntime);
+                return Some(id);
+            }
+        }
+    }
+
+    /// Returns the time slice of the current best-effort process under
+    /// `Policy::Fair`: an equal share of `FAIR_LATENCY` among the runnable
+    /// best-effort processes, but no less than `FAIR_MIN_GRANULARITY`.
+    fn fair_slice(&self) -> u64 {
+        let runnable = self.processes.values()
+            .filter(|p| p.realtime.is_none())
+            .filter(|p| match p.state {
+                State::Ready | State::Running => true,
+                _ => false
+            })
+            .count() as u64;
+
+        max(FAIR_LATENCY / max(runnable, 1), FAIR_MIN_GRANULARITY)
+    }
+
+    /// Returns the total time, in microseconds, spent idling in `switch()`
+    /// while no process was ready.
+    pub(super) fn idle_time(&self) -> u64 {
+        self.idle_time
+    }
+
+    /// Returns how long, in microseconds, the current process may run before
+    /// the scheduler has to decide again: a `TICK` (or a fair share under
+    /// `Policy::Fair`), cut short by the budget left to a real-time process
+    /// and by the start of the next real-time period, which may release a
+    /// process with an earlier deadline, and by the next expiration of an
+    /// alarm, which may wake a process waiting on it.
+    pub(super) fn time_slice(&self) -> u64 {
+        let now = self.hardware.now();
+        let mut slice = TICK as u64;
+        if let Some(current) = self.current.and_then(|id| self.processes.get(&id)) {
+            match current.realtime {
+                Some(rt) => slice = min(slice, rt.remaining()),
+                None if self.policy == Policy::Fair => slice = min(slice, self.fair_slice()),
+                None => ()
+            }
+        }
+
+        for process in self.processes.values() {
+            if let Some(ref rt) = process.realtime {
+                slice = min(slice, rt.deadline().saturating_sub(now));
+            }
+
+            for descriptor in process.descriptors.iter().filter_map(|d| d.as_ref()) {
+                if let Descriptor::Alarm(ref alarm) = *descriptor {
+                    if let Some(deadline) = alarm.deadline(now) {
+                        slice = min(slice, deadline.saturating_sub(now));
+                    }
+                }
+            }
+        }
+
+        max(slice, MIN_TIME_SLICE)
+    }
+
     /// Sets the current process's state to `new_state`, finds the next process
     /// to switch to, and performs the context switch on `tf` by saving `tf`
     /// into the current process and restoring the next process's trap frame
     /// into `tf`. If there is no current process, returns `None`. Otherwise,
     /// returns `Some` of the process ID that was context switched into `tf`.
     ///
+    /// The time the current process ran is charged to its real-time budget
+    /// or, for a best-effort process, added to its virtual runtime.
+    ///
+    /// If `new_state` is `State::Zombie`, the current process terminates: its
+    /// descriptors are closed and, unless it has a parent to reap it, it is
+    /// removed from the scheduler.
+    ///
+    /// FP/SIMD registers are switched lazily: switching to another process
+    /// disables its access to them, and they are loaded on its first use.
+    /// Software stepping is enabled only while a process being stepped runs.
+    ///
     /// This method blocks until there is a process to switch to, conserving
     /// energy as much as possible in the interim.
-    fn switch(&mut self, new_state: State, tf: &mut TrapFrame) -> Option<Id> {
-        if self.current.is_none() {
-            return None;
-        }
+    pub(super) fn switch(&mut self, new_state: State, tf: &mut TrapFrame) -> Option<Id> {
+        let id = match self.current {
+            Some(id) => id,
+            None => return None
+        };
 
         // Save link register for returning into HANDLER
-        // The actual link register for EL0 is saved in PSTATE
-        // `x30` here is for EL1
-        let x30 = tf.general_registers[1];
+        // It belongs to this exception, not to the current process
+        let handler_lr = tf.handler_lr();
 
-        // Move the current process to the back of the queue
-        let mut p = self.processes.pop_front().unwrap();
-        p.state = new_state;
-        mem::swap(tf, &mut *(p.trap_frame));
-        self.processes.push_back(p);
+        let elapsed = self.hardware.now() - self.slice_start;
         self.current = None;
+        self.queue.pop_front();
+
+        let alive = {
+            let p = self.processes.get_mut(&id).expect("current process");
+            // Trap frames are copied rather than swapped so that every process
+            // keeps its own frame (and thread ID) while it is running. The
+            // FP/SIMD registers in `tf` were only saved if the process could
+            // use them; otherwise its own copy is still current.
+            let fp_registers = p.trap_frame.floating_point_registers;
+            *p.trap_frame = *tf;
+            if !self.hardware.user_fp() {
+                p.trap_frame.floating_point_registers = fp_registers;
+            }
+
+            match p.realtime {
+                Some(ref mut rt) => rt.charge(elapsed),
+                None => p.vruntime += elapsed * 1000
+            }
+
+            match new_state {
+                State::Zombie(status) => {
+                    p.exit(status);
+                    p.parent.is_some()
+                },
+                new_state => {
+                    p.state = new_state;
+                    true
+                }
+            }
+        };
+
+        // Move the current process to the back of the queue
+        if alive {
+            self.queue.push_back(id);
+            self.enqueue(id);
+        } else {
+            self.processes.remove(&id);
+        }
 
         loop {
             // Find a ready process to execute
-            for i in 0..self.processes.len() {
-                if self.processes[i].is_ready() {
-                    // Process is ready!
-                    let mut process = self.processes.remove(i).unwrap();
-                    let id = process.trap_frame.thread_id as Id;
+            if let Some(next) = self.pick_next() {
+                // Process is ready!
+                {
+                    let process = self.processes.get_mut(&next).unwrap();
                     process.state = State::Running;
-                    // Keep `x30` (link register) from this exception
-                    process.trap_frame.general_registers[1] = x30;
+                    // Keep the link register from this exception
+                    process.trap_frame.set_handler_lr(handler_lr);
 
-                    // Move its trap frame into `tf`
-                    mem::swap(tf, &mut *(process.trap_frame));
-
-                    // Move it to the front of the queue
-                    self.processes.push_front(process);
+                    // Copy its trap frame into `tf`
+                    *tf = *process.trap_frame;
+                }
 
-                    // Mark it as current
-                    self.current = Some(id);
-                    return self.current.clone();
+                // Another process traps on its first FP/SIMD use, which loads
+                // its registers
+                if next != id {
+                    self.hardware.set_user_fp(false);
                 }
+
+                // Only a process being stepped may run with stepping enabled
+                self.hardware.restore_step(tf.spsr());
+
+                // Move it to the front of the queue
+                self.queue.retain(|&i| i != next);
+                self.queue.push_front(next);
+
+                // Mark it as current
+                self.current = Some(next);
+                self.slice_start = self.hardware.now();
+                return self.current.clone();
             }
 
-            aarch64::wait_for_interrupt();
+            let idle_start = self.hardware.now();
+            self.hardware.idle();
+            self.idle_time += self.hardware.now() - idle_start;
         }
     }
 }
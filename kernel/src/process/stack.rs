@@ -1,10 +1,24 @@
 use std::fmt;
 use std::ptr::Unique;
 
+#[cfg(not(test))]
 use ALLOCATOR;
 use alloc::allocator::{Alloc, Layout};
 use vm::PhysicalAddr;
 
+/// Returns the allocator stacks and other process memory are allocated from.
+#[cfg(not(test))]
+pub(super) fn allocator() -> &'static ::allocator::Allocator {
+    &ALLOCATOR
+}
+
+/// Returns the allocator stacks and other process memory are allocated from.
+/// Host tests have no kernel allocator and use the system heap instead.
+#[cfg(test)]
+pub(super) fn allocator() -> ::alloc::heap::Heap {
+    ::alloc::heap::Heap
+}
+
 /// A process stack. The default size is 1M1B with an alignment of 16 bytes.
 pub struct Stack {
     ptr: Unique<[u8; Stack::SIZE]>
@@ -27,7 +41,7 @@ impl Stack {
     /// fails for some other reason, returns `None`.
     pub fn new() -> Option<Stack> {
         let raw_ptr = unsafe {
-            let raw_ptr: *mut u8 = (&ALLOCATOR).alloc(Stack::layout()).ok()?;
+            let raw_ptr: *mut u8 = allocator().alloc(Stack::layout()).ok()?;
             raw_ptr.write_bytes(0, Self::SIZE);
             raw_ptr
         };
@@ -55,7 +69,7 @@ impl Stack {
 impl Drop for Stack {
     fn drop(&mut self) {
         unsafe {
-            (&ALLOCATOR).dealloc(self.as_mut_ptr(), Self::layout())
+            allocator().dealloc(self.as_mut_ptr(), Self::layout())
         }
     }
 }
@@ -9,6 +9,19 @@ use process::Process;
 /// called on the next time slice.
 pub type EventPollFn = Box<FnMut(&mut Process) -> bool + Send>;
 
+/// The reason a process terminated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process called `exit` with the given code.
+    Exited(u64),
+    /// The process was killed by another process.
+    Killed,
+    /// The process was killed because it took an exception other than a
+    /// system call, such as a data abort. Holds the exception's syndrome
+    /// (`ESR_EL1`).
+    Faulted(u32),
+}
+
 /// The scheduling state of a process.
 pub enum State {
     /// The process is ready to be scheduled.
@@ -17,6 +30,11 @@ pub enum State {
     Waiting(EventPollFn),
     /// The process is currently running.
     Running,
+    /// The process has been stopped and will not be scheduled until it is
+    /// resumed, at which point it returns to the wrapped state.
+    Stopped(Box<State>),
+    /// The process has terminated and is waiting to be reaped by its parent.
+    Zombie(ExitStatus),
 }
 
 impl fmt::Debug for State {
@@ -25,6 +43,8 @@ impl fmt::Debug for State {
             State::Ready => write!(f, "State::Ready"),
             State::Running => write!(f, "State::Running"),
             State::Waiting(_) => write!(f, "State::Waiting"),
+            State::Stopped(ref state) => write!(f, "State::Stopped({:?})", state),
+            State::Zombie(status) => write!(f, "State::Zombie({:?})", status),
         }
     }
 }
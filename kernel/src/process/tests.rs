@@ -0,0 +1,843 @@
+mod scheduler {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use process::{Process, State, ExitStatus, Id, Hardware, Policy, Descriptor, Alarm, RealTimeError, TICK};
+    use process::scheduler::Scheduler;
+    use traps::TrapFrame;
+    use aarch64::debug::SPSR_SS;
+    use console::Signal;
+
+    /// A fake machine. Time only moves forward when the CPU idles.
+    #[derive(Debug, Clone)]
+    struct MockHardware {
+        time: Arc<AtomicUsize>,
+        idles: Arc<AtomicUsize>,
+        fp: Arc<AtomicBool>,
+        step: Arc<AtomicBool>,
+    }
+
+    impl MockHardware {
+        /// The time, in microseconds, that passes on each idle.
+        const IDLE_US: usize = 1000;
+
+        fn new() -> MockHardware {
+            MockHardware {
+                time: Arc::new(AtomicUsize::new(0)),
+                idles: Arc::new(AtomicUsize::new(0)),
+                fp: Arc::new(AtomicBool::new(false)),
+                step: Arc::new(AtomicBool::new(false)),
+            }
+        }
+
+        fn idles(&self) -> usize {
+            self.idles.load(Ordering::SeqCst)
+        }
+
+        fn advance(&self, us: usize) {
+            self.time.fetch_add(us, Ordering::SeqCst);
+        }
+    }
+
+    impl Hardware for MockHardware {
+        fn now(&self) -> u64 {
+            self.time.load(Ordering::SeqCst) as u64
+        }
+
+        fn idle(&self) {
+            self.idles.fetch_add(1, Ordering::SeqCst);
+            self.time.fetch_add(Self::IDLE_US, Ordering::SeqCst);
+        }
+
+        fn user_fp(&self) -> bool {
+            self.fp.load(Ordering::SeqCst)
+        }
+
+        fn set_user_fp(&self, enabled: bool) {
+            self.fp.store(enabled, Ordering::SeqCst);
+        }
+
+        fn restore_step(&self, spsr: u64) {
+            self.step.store(spsr & SPSR_SS != 0, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns a process whose trap frame is tagged with `pc`, so that the
+    /// frame switched into `tf` can be identified.
+    fn process(pc: u64) -> Process {
+        let mut p = Process::new().expect("process");
+        p.trap_frame.program_counter = pc;
+        p
+    }
+
+    /// Returns a process that waits until `flag` is set.
+    fn waiting_on(pc: u64, flag: &Arc<AtomicBool>) -> Process {
+        let mut p = process(pc);
+        let flag = flag.clone();
+        p.state = State::Waiting(Box::new(move |_| flag.load(Ordering::SeqCst)));
+        p
+    }
+
+    /// Returns a round-robin scheduler with processes tagged `1..=n` and a
+    /// trap frame holding the state of the first one, as if it were running.
+    fn scheduler(n: u64) -> (Scheduler<MockHardware>, MockHardware, TrapFrame) {
+        scheduler_with(Policy::RoundRobin, n)
+    }
+
+    /// Like `scheduler()`, but for any `policy`.
+    fn scheduler_with(policy: Policy, n: u64) -> (Scheduler<MockHardware>, MockHardware, TrapFrame) {
+        let hw = MockHardware::new();
+        let mut s = Scheduler::new(hw.clone(), policy);
+        for pc in 1..(n + 1) {
+            s.add(process(pc)).expect("add");
+        }
+
+        let mut tf = TrapFrame::default();
+        tf.program_counter = 1;
+        tf.thread_id = 1;
+        (s, hw, tf)
+    }
+
+    #[test]
+    fn add_assigns_ids() {
+        let mut s = Scheduler::new(MockHardware::new(), Policy::RoundRobin);
+        assert!(s.current_mut().is_none());
+
+        let ids: Vec<Id> = (0..4).map(|i| s.add(process(i)).unwrap()).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+
+        // The first process added becomes the current one.
+        let current = s.current_mut().expect("current process");
+        assert_eq!(current.trap_frame.thread_id, 1);
+        assert_eq!(current.trap_frame.program_counter, 0);
+    }
+
+    #[test]
+    fn switch_without_processes() {
+        let mut s = Scheduler::new(MockHardware::new(), Policy::RoundRobin);
+        let mut tf = TrapFrame::default();
+        assert_eq!(s.switch(State::Ready, &mut tf), None);
+    }
+
+    #[test]
+    fn switch_round_robin() {
+        let (mut s, _, mut tf) = scheduler(3);
+
+        for &expected in [2, 3, 1, 2, 3, 1].iter() {
+            assert_eq!(s.switch(State::Ready, &mut tf), Some(expected));
+            assert_eq!(tf.thread_id, expected);
+            assert_eq!(tf.program_counter, expected);
+        }
+    }
+
+    #[test]
+    fn switch_saves_trap_frame() {
+        let (mut s, _, mut tf) = scheduler(2);
+
+        // Process 1 makes progress, then is switched out.
+        tf.program_counter = 0x1000;
+        tf.set_x(0, 0xdead);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        tf.program_counter = 0x2000;
+
+        // Its state comes back when it is switched in again.
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(tf.program_counter, 0x1000);
+        assert_eq!(tf.x(0), 0xdead);
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(tf.program_counter, 0x2000);
+    }
+
+    #[test]
+    fn switch_saves_fp_registers_in_use() {
+        let (mut s, hw, mut tf) = scheduler(2);
+
+        // Process 1 uses FP/SIMD registers, so they were saved in `tf`.
+        hw.set_user_fp(true);
+        tf.set_q(0, 42);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert!(!hw.user_fp(), "the next process must trap on its first FP use");
+
+        // Process 2 does not; whatever `tf` holds is not its state.
+        tf.set_q(0, 7);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(tf.q(0), 42);
+
+        let saved = s.process_mut(2).expect("process 2").trap_frame.q(0);
+        assert_eq!(saved, 0);
+    }
+
+    #[test]
+    fn switch_to_same_process_keeps_fp() {
+        let (mut s, hw, mut tf) = scheduler(1);
+        hw.set_user_fp(true);
+        tf.set_q(5, 99);
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert!(hw.user_fp());
+        assert_eq!(tf.q(5), 99);
+    }
+
+    #[test]
+    fn switch_enables_stepping_per_process() {
+        let (mut s, hw, mut tf) = scheduler(2);
+
+        // Process 1 is preempted before the instruction it steps.
+        tf.set_spsr(SPSR_SS);
+        hw.step.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert!(!hw.step.load(Ordering::SeqCst), "process 2 must not take the step");
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert!(hw.step.load(Ordering::SeqCst));
+        assert_eq!(tf.spsr() & SPSR_SS, SPSR_SS);
+    }
+
+    #[test]
+    fn switch_skips_waiting() {
+        let (mut s, _, mut tf) = scheduler(1);
+        let flag = Arc::new(AtomicBool::new(false));
+        let waiter = s.add(waiting_on(2, &flag)).unwrap();
+        s.add(process(3)).unwrap();
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+
+        // Once the event arrives, the waiting process is scheduled.
+        flag.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(waiter));
+        assert_eq!(tf.program_counter, 2);
+    }
+
+    #[test]
+    fn switch_into_waiting() {
+        let (mut s, _, mut tf) = scheduler(2);
+
+        // The poll function can set the return value of the waiting process.
+        let flag = Arc::new(AtomicBool::new(false));
+        let poll_flag = flag.clone();
+        let f = Box::new(move |p: &mut Process| {
+            p.trap_frame.set_x(0, 42);
+            poll_flag.load(Ordering::SeqCst)
+        });
+        assert_eq!(s.switch(State::Waiting(f), &mut tf), Some(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+
+        flag.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(tf.x(0), 42);
+    }
+
+    #[test]
+    fn switch_idles_until_ready() {
+        let (mut s, hw, mut tf) = scheduler(1);
+
+        // Wake up after the clock has advanced by three idles.
+        let time = hw.time.clone();
+        let f = Box::new(move |_: &mut Process| {
+            time.load(Ordering::SeqCst) >= 3 * MockHardware::IDLE_US
+        });
+        assert_eq!(s.switch(State::Waiting(f), &mut tf), Some(1));
+        assert_eq!(hw.idles(), 3);
+        assert_eq!(s.idle_time(), 3 * MockHardware::IDLE_US as u64);
+    }
+
+    #[test]
+    fn switch_fairness() {
+        const N: u64 = 5;
+        const ROUNDS: usize = 20;
+        let (mut s, hw, mut tf) = scheduler(N);
+
+        let mut runs = [0usize; N as usize + 1];
+        for _ in 0..(N as usize * ROUNDS) {
+            let id = s.switch(State::Ready, &mut tf).unwrap();
+            runs[id as usize] += 1;
+        }
+
+        for id in 1..(N as usize + 1) {
+            assert_eq!(runs[id], ROUNDS, "process {} ran {} times", id, runs[id]);
+        }
+        assert_eq!(hw.idles(), 0);
+    }
+
+    #[test]
+    fn exit_and_reap() {
+        let (mut s, _, mut tf) = scheduler(1);
+        let mut child = process(2);
+        child.parent = Some(1);
+        let child = s.add(child).unwrap();
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(child));
+        assert_eq!(s.reap(child), None);
+
+        // A child becomes a zombie until its parent reaps it.
+        let exit = State::Zombie(ExitStatus::Exited(7));
+        assert_eq!(s.switch(exit, &mut tf), Some(1));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(s.reap(child), Some(ExitStatus::Exited(7)));
+        assert_eq!(s.reap(child), None);
+        assert!(!s.kill(child));
+    }
+
+    #[test]
+    fn kill_stop_resume() {
+        let (mut s, _, mut tf) = scheduler(3);
+
+        // The running process cannot be killed or stopped directly.
+        assert!(!s.kill(1));
+        assert!(!s.stop(1));
+
+        assert!(s.stop(2));
+        assert!(s.is_stopped(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        assert!(s.resume(2));
+        assert!(!s.resume(2));
+        assert!(!s.is_stopped(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        // Processes without a parent are removed as soon as they are killed.
+        assert!(s.kill(3));
+        assert!(!s.kill(3));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+    }
+
+    #[test]
+    fn stop_and_kill_children() {
+        let (mut s, _, mut tf) = scheduler(1);
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut child = waiting_on(2, &flag);
+        child.parent = Some(1);
+        let child = s.add(child).unwrap();
+
+        // A stopped process keeps waiting once it is resumed.
+        assert!(s.stop(child));
+        assert!(!s.stop(child));
+        flag.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert!(s.resume(child));
+        flag.store(false, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        flag.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(child));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        // A killed child stays a zombie, which cannot be stopped or resumed,
+        // until it is reaped.
+        assert!(s.kill(child));
+        assert!(!s.stop(child));
+        assert!(!s.resume(child));
+        assert!(!s.is_stopped(child));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(s.reap(child), Some(ExitStatus::Killed));
+        assert!(!s.resume(child));
+    }
+
+    #[test]
+    fn signal_running_process() {
+        let (mut s, _, mut tf) = scheduler(1);
+        let mut child = process(2);
+        child.parent = Some(1);
+        let child = s.add(child).unwrap();
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(child));
+
+        // Ctrl-Z while the child runs switches it out, stopped.
+        tf.program_counter = 0x2000;
+        assert!(s.signal(child, Signal::Stop, &mut tf));
+        assert_eq!(tf.program_counter, 1);
+        assert!(s.is_stopped(child));
+        assert!(!s.signal(child, Signal::Stop, &mut tf));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        // It resumes where it stopped.
+        assert!(s.resume(child));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(child));
+        assert_eq!(tf.program_counter, 0x2000);
+
+        // Ctrl-C while it runs leaves a zombie for its parent.
+        assert!(s.signal(child, Signal::Interrupt, &mut tf));
+        assert_eq!(tf.program_counter, 1);
+        assert!(!s.signal(child, Signal::Interrupt, &mut tf));
+        assert_eq!(s.reap(child), Some(ExitStatus::Killed));
+    }
+
+    #[test]
+    fn signal_other_process() {
+        let (mut s, _, mut tf) = scheduler(2);
+        tf.program_counter = 0x1000;
+
+        // Another process is stopped or killed without a switch.
+        assert!(s.signal(2, Signal::Stop, &mut tf));
+        assert_eq!(tf.program_counter, 0x1000);
+        assert!(s.is_stopped(2));
+        assert!(s.signal(2, Signal::Interrupt, &mut tf));
+        assert!(!s.signal(2, Signal::Stop, &mut tf));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+    }
+
+    #[test]
+    fn realtime_admission_control() {
+        let (mut s, _, _) = scheduler(3);
+
+        assert_eq!(s.set_realtime(1, 1000, 0), Err(RealTimeError::Invalid));
+        assert_eq!(s.set_realtime(1, 1000, 1001), Err(RealTimeError::Invalid));
+        assert_eq!(s.set_realtime(42, 1000, 500), Err(RealTimeError::NoProcess));
+        assert_eq!(s.set_realtime(42, 0, 0), Err(RealTimeError::NoProcess));
+
+        assert_eq!(s.set_realtime(1, 1000, 500), Ok(()));
+        assert_eq!(s.set_realtime(2, 1000, 600), Err(RealTimeError::Rejected));
+        assert_eq!(s.set_realtime(2, 2000, 1000), Ok(()));
+        assert_eq!(s.set_realtime(3, 1000 * 1000, 1), Err(RealTimeError::Rejected));
+
+        // Changing the parameters of a process replaces its old utilisation.
+        assert_eq!(s.set_realtime(1, 1000, 200), Ok(()));
+        assert_eq!(s.set_realtime(3, 1000, 300), Ok(()));
+
+        // Leaving the real-time class frees up its share.
+        assert_eq!(s.set_realtime(2, 0, 0), Ok(()));
+        assert_eq!(s.set_realtime(3, 1000, 800), Ok(()));
+    }
+
+    #[test]
+    fn realtime_before_best_effort() {
+        let (mut s, _, mut tf) = scheduler(3);
+        assert_eq!(s.set_realtime(3, 100 * 1000, 10 * 1000), Ok(()));
+
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+
+        // Blocked real-time processes let best-effort ones run.
+        let flag = Arc::new(AtomicBool::new(false));
+        let poll_flag = flag.clone();
+        let f = Box::new(move |_: &mut Process| poll_flag.load(Ordering::SeqCst));
+        assert_eq!(s.switch(State::Waiting(f), &mut tf), Some(2));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        flag.store(true, Ordering::SeqCst);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+    }
+
+    #[test]
+    fn realtime_earliest_deadline_first() {
+        let (mut s, hw, mut tf) = scheduler(3);
+        assert_eq!(s.set_realtime(2, 10 * 1000, 1000), Ok(()));
+        assert_eq!(s.set_realtime(3, 5 * 1000, 1000), Ok(()));
+
+        // Process 3 has the earliest deadline; its budget bounds the slice.
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        assert_eq!(s.time_slice(), 1000);
+
+        // Once its budget is used up, it is throttled until its next period.
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        assert_eq!(s.time_slice(), 3000);
+
+        // A new period refills the budget and moves the deadline.
+        hw.advance(3000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+    }
+
+    #[test]
+    fn alarm_bounds_time_slice() {
+        let (mut s, hw, mut tf) = scheduler(2);
+        let alarm = Alarm::new();
+        s.current_mut().unwrap().add_descriptor(Descriptor::Alarm(alarm.clone()));
+        assert_eq!(s.time_slice(), TICK as u64);
+
+        // The alarm of a process that is not running counts as well.
+        alarm.set(hw.now(), 500, 0);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(s.time_slice(), 500);
+
+        hw.advance(500);
+        assert_eq!(alarm.take_expirations(hw.now()), 1);
+        assert_eq!(s.time_slice(), TICK as u64);
+    }
+
+    #[test]
+    fn unread_alarm_does_not_shorten_slices() {
+        let (mut s, hw, _) = scheduler(2);
+        let alarm = Alarm::new();
+        s.current_mut().unwrap().add_descriptor(Descriptor::Alarm(alarm.clone()));
+
+        // A periodic alarm that expires but is never read.
+        alarm.set(hw.now(), 500, 10 * TICK as u64);
+        assert_eq!(s.time_slice(), 500);
+        hw.advance(600);
+        assert_eq!(s.time_slice(), TICK as u64);
+        assert_eq!(alarm.take_expirations(hw.now()), 1);
+    }
+
+    #[test]
+    fn policy_from_cmdline() {
+        assert_eq!(Policy::from_cmdline("sched=fair"), Some(Policy::Fair));
+        assert_eq!(Policy::from_cmdline("quiet sched=cfs"), Some(Policy::Fair));
+        assert_eq!(Policy::from_cmdline("sched=rr console=ttyS0"), Some(Policy::RoundRobin));
+        assert_eq!(Policy::from_cmdline("console=ttyS0 sched="), None);
+        assert_eq!(Policy::from_cmdline(""), None);
+    }
+
+    #[test]
+    fn fair_picks_min_vruntime() {
+        let (mut s, hw, mut tf) = scheduler_with(Policy::Fair, 3);
+
+        // Ties are broken by process ID.
+        hw.advance(3000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        hw.advance(2000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        hw.advance(2000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+    }
+
+    #[test]
+    fn fair_sleeper_credit() {
+        let (mut s, hw, mut tf) = scheduler_with(Policy::Fair, 2);
+
+        // Process 1 blocks while process 2 runs for 200ms.
+        let flag = Arc::new(AtomicBool::new(false));
+        let poll_flag = flag.clone();
+        let f = Box::new(move |_: &mut Process| poll_flag.load(Ordering::SeqCst));
+        assert_eq!(s.switch(State::Waiting(f), &mut tf), Some(2));
+        hw.advance(100 * 1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        hw.advance(100 * 1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+
+        // On wake up, process 1 runs first, but only for its sleeper credit
+        // plus what process 2 ran since, not for the whole 200ms.
+        flag.store(true, Ordering::SeqCst);
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        hw.advance(5 * 1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        hw.advance(10 * 1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+    }
+
+    #[test]
+    fn fair_shares_cpu_time() {
+        const N: usize = 4;
+        let (mut s, hw, mut tf) = scheduler_with(Policy::Fair, N as u64);
+        assert_eq!(s.time_slice(), 5000);
+
+        // Processes use their slices unevenly, yet end up with the same time.
+        let mut runtime = [0usize; N + 1];
+        let mut current = 1;
+        for i in 0..400 {
+            let used = 500 + (current * 300 + i * 7) % 1000;
+            hw.advance(used);
+            runtime[current] += used;
+            current = s.switch(State::Ready, &mut tf).unwrap() as usize;
+        }
+
+        let most = runtime.iter().max().unwrap();
+        let least = runtime[1..].iter().min().unwrap();
+        assert!(most - least <= 1500, "unfair runtimes: {:?}", runtime);
+    }
+
+    #[test]
+    fn fair_polls_waiters() {
+        let (mut s, hw, mut tf) = scheduler_with(Policy::Fair, 2);
+        let flag = Arc::new(AtomicBool::new(false));
+        let polls = Arc::new(AtomicUsize::new(0));
+        let (poll_flag, poll_count) = (flag.clone(), polls.clone());
+        let mut p = process(3);
+        p.state = State::Waiting(Box::new(move |_| {
+            poll_count.fetch_add(1, Ordering::SeqCst);
+            poll_flag.load(Ordering::SeqCst)
+        }));
+        assert_eq!(s.add(p), Some(3));
+
+        // The waiter is polled on every pick until its event arrives.
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(polls.load(Ordering::SeqCst), 1);
+        flag.store(true, Ordering::SeqCst);
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+
+        // Then it is ready, and never polled again.
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fair_stop_resume() {
+        let (mut s, hw, mut tf) = scheduler_with(Policy::Fair, 3);
+
+        assert!(s.stop(2));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(3));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(1));
+
+        // The resumed process has the smallest virtual runtime.
+        assert!(s.resume(2));
+        hw.advance(1000);
+        assert_eq!(s.switch(State::Ready, &mut tf), Some(2));
+    }
+}
+
+mod mapping {
+    use process::{Process, Mapping, ExitStatus};
+
+    #[test]
+    fn mapping_rounds_to_pages() {
+        let m = Mapping::new(1).expect("mapping");
+        assert_eq!(m.size(), Mapping::PAGE_SIZE);
+        assert_eq!(m.start() % Mapping::PAGE_SIZE, 0);
+
+        let m = Mapping::new(Mapping::PAGE_SIZE + 1).expect("mapping");
+        assert_eq!(m.size(), 2 * Mapping::PAGE_SIZE);
+
+        assert!(Mapping::new(0).is_none());
+        assert!(Mapping::new(Mapping::MAX_SIZE + 1).is_none());
+    }
+
+    #[test]
+    fn mapping_is_zeroed() {
+        let m = Mapping::new(100).expect("mapping");
+        let bytes = unsafe { ::std::slice::from_raw_parts(m.start() as *const u8, m.size()) };
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn mapping_contains() {
+        let m = Mapping::new(Mapping::PAGE_SIZE).expect("mapping");
+        let (start, size) = (m.start(), m.size());
+        assert!(m.contains(start, size));
+        assert!(m.contains(start + size - 1, 1));
+        assert!(m.contains(start + size, 0));
+        assert!(!m.contains(start + size, 1));
+        assert!(!m.contains(start - 1, 1));
+        assert!(!m.contains(start, size + 1));
+    }
+
+    #[test]
+    fn map_unmap_and_exit() {
+        let mut p = Process::new().expect("process");
+        let a = p.map(10).expect("map");
+        let b = p.map(10).expect("map");
+        assert_ne!(a, b);
+        assert_eq!(p.mappings.len(), 2);
+
+        assert!(!p.unmap(a + 1));
+        assert!(p.unmap(a));
+        assert!(!p.unmap(a));
+        assert_eq!(p.mappings.len(), 1);
+
+        p.exit(ExitStatus::Exited(0));
+        assert!(p.mappings.is_empty());
+    }
+}
+
+mod futex {
+    use process::Futexes;
+
+    #[test]
+    fn wake_in_order() {
+        let mut f = Futexes::new();
+        let a = f.wait(0x1000);
+        let b = f.wait(0x1000);
+        let c = f.wait(0x1000);
+        assert_eq!(f.waiters(0x1000), 3);
+
+        assert_eq!(f.wake(0x1000, 2), 2);
+        assert!(a.woken() && b.woken() && !c.woken());
+        assert_eq!(f.waiters(0x1000), 1);
+
+        assert_eq!(f.wake(0x1000, 5), 1);
+        assert!(c.woken());
+        assert_eq!(f.waiters(0x1000), 0);
+        assert_eq!(f.wake(0x1000, 1), 0);
+    }
+
+    #[test]
+    fn keyed_by_address() {
+        let mut f = Futexes::new();
+        let a = f.wait(0x1000);
+        let b = f.wait(0x2000);
+
+        assert_eq!(f.wake(0x3000, 1), 0);
+        assert_eq!(f.wake(0x2000, 1), 1);
+        assert!(!a.woken() && b.woken());
+        assert_eq!(f.waiters(0x1000), 1);
+    }
+
+    #[test]
+    fn dropped_waiters_are_skipped() {
+        let mut f = Futexes::new();
+        let a = f.wait(0x1000);
+        let b = f.wait(0x1000);
+        drop(a);
+        assert_eq!(f.waiters(0x1000), 1);
+
+        assert_eq!(f.wake(0x1000, 1), 1);
+        assert!(b.woken());
+
+        drop(f.wait(0x1000));
+        assert_eq!(f.wake(0x1000, 1), 0);
+    }
+}
+
+mod alarm {
+    use process::Alarm;
+
+    #[test]
+    fn one_shot() {
+        let alarm = Alarm::new();
+        assert_eq!(alarm.deadline(1000), None);
+        assert_eq!(alarm.take_expirations(1000), 0);
+
+        alarm.set(1000, 500, 0);
+        assert_eq!(alarm.deadline(1000), Some(1500));
+        assert_eq!(alarm.take_expirations(1499), 0);
+        assert_eq!(alarm.take_expirations(1500), 1);
+        assert_eq!(alarm.deadline(1500), None);
+        assert_eq!(alarm.take_expirations(5000), 0);
+    }
+
+    #[test]
+    fn periodic() {
+        let alarm = Alarm::new();
+        alarm.set(0, 100, 50);
+        assert_eq!(alarm.take_expirations(100), 1);
+        assert_eq!(alarm.deadline(100), Some(150));
+
+        // Missed periods are all counted.
+        assert_eq!(alarm.take_expirations(260), 3);
+        assert_eq!(alarm.deadline(260), Some(300));
+    }
+
+    #[test]
+    fn deadline_moves_past_unread_expirations() {
+        let alarm = Alarm::new();
+        alarm.set(0, 100, 50);
+        assert_eq!(alarm.deadline(260), Some(300));
+        assert_eq!(alarm.take_expirations(260), 4);
+
+        alarm.set(0, 100, 0);
+        assert_eq!(alarm.deadline(100), None);
+        assert_eq!(alarm.take_expirations(100), 1);
+    }
+
+    #[test]
+    fn disarm_discards_expirations() {
+        let alarm = Alarm::new();
+        alarm.set(0, 100, 100);
+        alarm.set(200, 0, 0);
+        assert_eq!(alarm.deadline(200), None);
+        assert_eq!(alarm.take_expirations(1000), 0);
+    }
+
+    #[test]
+    fn read_count() {
+        let alarm = Alarm::new();
+        let mut buf = [0u8; 8];
+        assert!(alarm.read(0, &mut buf[..4]).is_err());
+        assert!(alarm.read(0, &mut buf).is_err());
+
+        alarm.set(0, 10, 10);
+        assert_eq!(alarm.read(25, &mut buf).unwrap(), 8);
+        assert_eq!(buf, [2, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
+
+mod pipe {
+    use std::io::ErrorKind;
+    use process::{pipe, PIPE_CAPACITY};
+
+    #[test]
+    fn empty() {
+        let (reader, _writer) = pipe();
+        let mut buf = [0u8; 16];
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(reader.read(&mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn full() {
+        let (reader, writer) = pipe();
+        let data = vec![7u8; PIPE_CAPACITY + 10];
+        assert_eq!(writer.write(&data).unwrap(), PIPE_CAPACITY);
+        assert_eq!(writer.write(&data).unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(writer.write(&[]).unwrap(), 0);
+
+        let mut buf = [0u8; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 10);
+        assert_eq!(writer.write(&data).unwrap(), 10);
+        assert_eq!(writer.write(&data).unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn wraparound() {
+        let (reader, writer) = pipe();
+        let mut buf = vec![0u8; PIPE_CAPACITY];
+
+        // Move the head close to the end of the buffer.
+        let start = PIPE_CAPACITY - 3;
+        assert_eq!(writer.write(&buf[..start]).unwrap(), start);
+        assert_eq!(reader.read(&mut buf[..start]).unwrap(), start);
+
+        let data: Vec<u8> = (0..PIPE_CAPACITY).map(|i| i as u8).collect();
+        assert_eq!(writer.write(&data).unwrap(), PIPE_CAPACITY);
+        assert_eq!(reader.read(&mut buf[..5]).unwrap(), 5);
+        assert_eq!(&buf[..5], &data[..5]);
+
+        assert_eq!(writer.write(&data[..5]).unwrap(), 5);
+        assert_eq!(reader.read(&mut buf).unwrap(), PIPE_CAPACITY);
+        assert_eq!(&buf[..PIPE_CAPACITY - 5], &data[5..]);
+        assert_eq!(&buf[PIPE_CAPACITY - 5..], &data[..5]);
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn eof_after_writers_close() {
+        let (reader, writer) = pipe();
+        let other = writer.clone();
+        assert_eq!(writer.write(b"abc").unwrap(), 3);
+        drop(writer);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        // Buffered data is still read after the last writer closes.
+        assert_eq!(other.write(b"de").unwrap(), 2);
+        drop(other);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"de");
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn broken_pipe_after_readers_close() {
+        let (reader, writer) = pipe();
+        let other = reader.clone();
+        drop(reader);
+        assert_eq!(writer.write(b"abc").unwrap(), 3);
+
+        drop(other);
+        assert_eq!(writer.write(b"abc").unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(writer.write(&[]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -1,4 +1,5 @@
 use aarch64;
+use aarch64::debug::{self, Access};
 use stack_vec::StackVec;
 use console::{kprint, kprintln, CONSOLE};
 use std::io::Write;
@@ -6,8 +7,12 @@ use std::str;
 use std::path::{Path, PathBuf};
 use fat32::vfat::*;
 use fat32::traits::{FileSystem, Entry, Dir, Metadata, Timestamp};
-use traps::syscall;
-use super::FILE_SYSTEM;
+use traps::{self, dump_registers, Syndrome, TrapFrame};
+use pi::common::IO_BASE;
+use process::{Process, ExitStatus, Id};
+use gdb;
+use ulib;
+use super::{FILE_SYSTEM, SCHEDULER};
 
 const SHELL_WELCOME: &'static str = r#"
   _____     _     _                ____   _____ 
@@ -76,32 +81,463 @@ static SHELL_CMDS: &'static [&'static ShellCmd] = &[
     &CatCmd,
     &CurrentELCmd,
     &ExceptionCmd,
-    &SleepCmd
+    &GdbCmd,
+    &SleepCmd,
+    &IrqStatCmd,
+    &SyscallBenchCmd,
+    &StraceCmd
 ];
 
+// Find the corresponding command
+// from the registered command list
+fn find_command(name: &str) -> Option<&'static ShellCmd> {
+    for shell_cmd in SHELL_CMDS {
+        if shell_cmd.name() == name {
+            return Some(*shell_cmd);
+        }
+    }
+
+    None
+}
+
 // Process a command received from shell
 fn process_command(pwd: &mut PathBuf, cmd: Command) {
-    let cmd_name = cmd.path();
+    match find_command(cmd.path()) {
+        Some(shell_cmd) => shell_cmd.exec(pwd, &cmd),
+        None => kprintln!("error: unknown command: {}", cmd.path())
+    }
+}
 
-    // Find the corresponding command
-    // from the registered command list
-    for shell_cmd in SHELL_CMDS {
-        if shell_cmd.name() == cmd.path() {
-            shell_cmd.exec(pwd, &cmd);
-            return;
+// How often (in milliseconds) the shell polls a foreground job
+const JOB_POLL_MS: u32 = 10;
+
+/// Everything a job process needs to run its command.
+struct JobSpec {
+    cmd: &'static ShellCmd,
+    pwd: PathBuf,
+    line: String,
+}
+
+// Entry point of job processes
+// `spec` is passed in `x0` and owned by the job
+extern "C" fn job_entry(spec: *mut JobSpec) {
+    let JobSpec { cmd, mut pwd, line } = *unsafe { Box::from_raw(spec) };
+    let mut cmd_buf = [""; 64];
+    if let Ok(args) = Command::parse(&line, &mut cmd_buf[..]) {
+        cmd.exec(&mut pwd, &args);
+    }
+}
+
+/// A command started by the shell as a separate process.
+struct Job {
+    number: usize,
+    pid: Id,
+    line: String,
+}
+
+/// The table of jobs of a shell with job control.
+struct Jobs {
+    list: Vec<Job>,
+    finished: Vec<(Job, ExitStatus)>,
+}
+
+impl Jobs {
+    fn new() -> Jobs {
+        Jobs { list: Vec::new(), finished: Vec::new() }
+    }
+
+    /// Runs `cmd`, parsed from `line`. Built-in commands run in the shell
+    /// itself; everything else is started as a new process, which the shell
+    /// waits for unless `background` is set. The system calls of the new
+    /// process are traced if `traced` is set.
+    fn run(&mut self, pwd: &mut PathBuf, cmd: Command, line: &str, background: bool, traced: bool) {
+        match cmd.path() {
+            "jobs" => return self.list_jobs(),
+            "fg" => return self.foreground(cmd.arguments()),
+            "strace" if cmd.arguments().first() == Some(&"run") => {
+                // $ strace run <command>
+                // start `command` with its system calls traced
+                let line = cmd.arguments()[1..].join(" ");
+                let mut cmd_buf = [""; 64];
+                return match Command::parse(&line, &mut cmd_buf[..]) {
+                    Ok(cmd) => self.run(pwd, cmd, &line, background, true),
+                    Err(_) => kprintln!("usage: strace run <command>")
+                };
+            },
+            _ => ()
+        }
+
+        let shell_cmd = match find_command(cmd.path()) {
+            Some(shell_cmd) => shell_cmd,
+            None => return kprintln!("error: unknown command: {}", cmd.path())
+        };
+
+        if shell_cmd.builtin() {
+            if traced {
+                return kprintln!("error: cannot trace built-in command `{}`", cmd.path());
+            }
+            return shell_cmd.exec(pwd, &cmd);
+        }
+
+        let pid = match Jobs::spawn(shell_cmd, pwd, line, traced) {
+            Some(pid) => pid,
+            None => return kprintln!("error: unable to start `{}`", cmd.path())
+        };
+
+        let number = self.list.iter().map(|j| j.number).max().unwrap_or(0) + 1;
+        self.list.push(Job { number, pid, line: line.to_string() });
+        if background {
+            kprintln!("[{}] {}", number, pid);
+        } else {
+            let i = self.list.len() - 1;
+            self.wait(i);
         }
     }
 
-    kprintln!("error: unknown command: {}", cmd_name);
+    /// Starts `cmd` as a child process of the shell.
+    fn spawn(cmd: &'static ShellCmd, pwd: &PathBuf, line: &str, traced: bool) -> Option<Id> {
+        let mut process = Process::create_process(job_entry as *const ())?;
+        let spec = Box::into_raw(Box::new(JobSpec {
+            cmd,
+            pwd: pwd.clone(),
+            line: line.to_string()
+        }));
+        process.trap_frame.set_x(0, spec as u64);
+        process.parent = SCHEDULER.current();
+        process.traced = traced;
+
+        let pid = SCHEDULER.add(process);
+        if pid.is_none() {
+            drop(unsafe { Box::from_raw(spec) });
+        }
+        pid
+    }
+
+    /// Waits for the job at index `i` to terminate or to be stopped. It is
+    /// the foreground process meanwhile: the console interrupt kills it on
+    /// Ctrl-C and stops it on Ctrl-Z.
+    fn wait(&mut self, i: usize) {
+        let pid = self.list[i].pid;
+        CONSOLE.lock().set_foreground(Some(pid));
+        loop {
+            if let Some(status) = SCHEDULER.reap(pid) {
+                let job = self.list.remove(i);
+                match status {
+                    ExitStatus::Exited(0) => (),
+                    ExitStatus::Killed => kprintln!("^C"),
+                    ExitStatus::Exited(code) => kprintln!("[{}] Exit {}\t{}", job.number, code, job.line),
+                    ExitStatus::Faulted(esr) => {
+                        kprintln!("[{}] Fault {:?}\t{}", job.number, Syndrome::from(esr), job.line)
+                    }
+                }
+                break;
+            }
+
+            if SCHEDULER.is_stopped(pid) {
+                kprintln!("^Z");
+                kprintln!("[{}] Stopped\t{}", self.list[i].number, self.list[i].line);
+                break;
+            }
+
+            ulib::sleep(JOB_POLL_MS);
+        }
+        CONSOLE.lock().set_foreground(None);
+    }
+
+    /// Reads a byte typed at the prompt, reaping terminated background jobs
+    /// while none is available.
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            {
+                let mut console = CONSOLE.lock();
+                if console.has_byte() {
+                    return console.read_byte();
+                }
+            }
+
+            self.reap();
+            ulib::sleep(JOB_POLL_MS);
+        }
+    }
+
+    /// Reaps terminated background jobs, keeping them to be reported.
+    fn reap(&mut self) {
+        let mut i = 0;
+        while i < self.list.len() {
+            match SCHEDULER.reap(self.list[i].pid) {
+                Some(status) => {
+                    let job = self.list.remove(i);
+                    self.finished.push((job, status));
+                },
+                None => i += 1
+            }
+        }
+    }
+
+    /// Reaps terminated background jobs and reports every job that
+    /// terminated since the last prompt.
+    fn reap_finished(&mut self) {
+        self.reap();
+        for (job, status) in self.finished.drain(..) {
+            match status {
+                ExitStatus::Exited(0) => kprintln!("[{}] Done\t{}", job.number, job.line),
+                ExitStatus::Exited(code) => kprintln!("[{}] Exit {}\t{}", job.number, code, job.line),
+                ExitStatus::Killed => kprintln!("[{}] Killed\t{}", job.number, job.line),
+                ExitStatus::Faulted(esr) => {
+                    kprintln!("[{}] Fault {:?}\t{}", job.number, Syndrome::from(esr), job.line)
+                }
+            }
+        }
+    }
+
+    // $ jobs
+    // list running and stopped jobs
+    fn list_jobs(&mut self) {
+        for job in self.list.iter() {
+            let state = if SCHEDULER.is_stopped(job.pid) { "Stopped" } else { "Running" };
+            kprintln!("[{}] {}\t{}", job.number, state, job.line);
+        }
+    }
+
+    // $ fg [n]
+    // resume job `n` (the most recent job by default) in the foreground
+    fn foreground(&mut self, args: &[&str]) {
+        let i = match args.len() {
+            0 if !self.list.is_empty() => Some(self.list.len() - 1),
+            0 => return kprintln!("error: no current job"),
+            1 => match args[0].trim_left_matches('%').parse::<usize>() {
+                Ok(number) => self.list.iter().position(|j| j.number == number),
+                Err(_) => return kprintln!("error: invalid job number: {}", args[0])
+            },
+            _ => return kprintln!("error: too many arguments")
+        };
+
+        match i {
+            Some(i) => {
+                kprintln!("{}", self.list[i].line);
+                SCHEDULER.resume(self.list[i].pid);
+                self.wait(i);
+            },
+            None => kprintln!("error: no such job: {}", args[0])
+        }
+    }
+}
+
+/// What the debugger does when the debug shell returns.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resume {
+    /// Resume execution.
+    Continue,
+    /// Execute a single instruction, then return to the debugger.
+    Step,
 }
 
-/// Starts a shell using `prefix` as the prefix for each line. This function
+/// The debugger of a debug shell: commands that inspect and modify the state
+/// of the code that took an exception.
+struct Debugger<'a> {
+    tf: &'a mut TrapFrame,
+}
+
+impl<'a> Debugger<'a> {
+    /// Runs `cmd` if it is a debugger command. Returns `Some` if the shell
+    /// should return and resume execution, and `None` otherwise; commands
+    /// other than debugger commands are run synchronously.
+    fn run(&mut self, pwd: &mut PathBuf, cmd: Command) -> Option<Resume> {
+        let handled = match cmd.path() {
+            "continue" | "c" => return Some(Resume::Continue),
+            "step" | "s" => return Some(Resume::Step),
+            "regs" => { self.regs(); true },
+            "set" => { self.set(cmd.arguments()); true },
+            "break" => { self.breakpoint(cmd.arguments()); true },
+            "watch" => { self.watchpoint(cmd.arguments()); true },
+            path if path == "x" || path.starts_with("x/") => {
+                self.examine(path, cmd.arguments());
+                true
+            },
+            _ => false
+        };
+
+        if !handled {
+            process_command(pwd, cmd);
+        }
+        None
+    }
+
+    // $ regs
+    // show all registers of the trapped code
+    fn regs(&self) {
+        kprintln!("pc   {:016x}  sp   {:016x}  spsr {:016x}",
+                  self.tf.elr(), self.tf.stack_pointer, self.tf.spsr());
+        dump_registers(&*self.tf);
+    }
+
+    // $ set <reg> <val>
+    // set `x0`-`x30`, `lr`, `sp`, `pc` or `spsr`
+    fn set(&mut self, args: &[&str]) {
+        if args.len() != 2 {
+            return kprintln!("usage: set <reg> <val>");
+        }
+
+        let value = match parse_number(args[1]) {
+            Some(value) => value,
+            None => return kprintln!("error: invalid value: {}", args[1])
+        };
+
+        match args[0] {
+            "lr" => self.tf.set_lr(value),
+            "sp" => self.tf.stack_pointer = value,
+            "pc" | "elr" => self.tf.set_elr(value),
+            "spsr" => self.tf.set_spsr(value),
+            reg => match reg.trim_left_matches('x').parse::<usize>() {
+                Ok(n) if reg.starts_with('x') && n <= 30 => self.tf.set_x(n, value),
+                _ => kprintln!("error: unknown register: {}", reg)
+            }
+        }
+    }
+
+    // $ break [<addr> | -d <n>]
+    // list breakpoints, set one at `addr` or clear breakpoint `n`
+    fn breakpoint(&self, args: &[&str]) {
+        unsafe {
+            match args.len() {
+                0 => for n in 0..debug::breakpoints() {
+                    if let Some(addr) = debug::breakpoint(n) {
+                        kprintln!("{}: {:#x}", n, addr);
+                    }
+                },
+                1 => match parse_number(args[0]) {
+                    Some(addr) => match debug::set_breakpoint(addr) {
+                        Some(n) => kprintln!("breakpoint {} at {:#x}", n, addr & !0b11),
+                        None => kprintln!("error: all breakpoints are in use")
+                    },
+                    None => kprintln!("error: invalid address: {}", args[0])
+                },
+                2 if args[0] == "-d" => match args[1].parse::<usize>() {
+                    Ok(n) if debug::clear_breakpoint(n) => (),
+                    _ => kprintln!("error: no such breakpoint: {}", args[1])
+                },
+                _ => kprintln!("usage: break [<addr> | -d <n>]")
+            }
+        }
+    }
+
+    // $ watch [<addr> [r|w|rw] | -d <n>]
+    // list watchpoints, watch the doubleword at `addr` or clear watchpoint `n`
+    fn watchpoint(&self, args: &[&str]) {
+        if args.len() == 2 && args[0] == "-d" {
+            return match args[1].parse::<usize>() {
+                Ok(n) if unsafe { debug::clear_watchpoint(n) } => (),
+                _ => kprintln!("error: no such watchpoint: {}", args[1])
+            };
+        }
+
+        let access = match args.get(1).map(|s| *s) {
+            None | Some("rw") => Access::ReadWrite,
+            Some("r") => Access::Read,
+            Some("w") => Access::Write,
+            Some(kind) => return kprintln!("error: invalid access: {}", kind)
+        };
+
+        unsafe {
+            match args.len() {
+                0 => for n in 0..debug::watchpoints() {
+                    if let Some((addr, access)) = debug::watchpoint(n) {
+                        kprintln!("{}: {:#x} {:?}", n, addr, access);
+                    }
+                },
+                1 | 2 => match parse_number(args[0]) {
+                    Some(addr) => match debug::set_watchpoint(addr, access) {
+                        Some(n) => kprintln!("watchpoint {} at {:#x}", n, addr & !0b111),
+                        None => kprintln!("error: all watchpoints are in use")
+                    },
+                    None => kprintln!("error: invalid address: {}", args[0])
+                },
+                _ => kprintln!("usage: watch [<addr> [r|w|rw] | -d <n>]")
+            }
+        }
+    }
+
+    // $ x/<n> <addr>
+    // show `n` (default 1) 64-bit words of memory at `addr`
+    fn examine(&self, path: &str, args: &[&str]) {
+        let count = match path.len() {
+            1 => Some(1),
+            _ => path[2..].parse::<usize>().ok()
+        };
+
+        let (count, addr) = match (count, args.len()) {
+            (Some(count), 1) => match parse_number(args[0]) {
+                Some(addr) => (count, addr as usize & !0x7),
+                None => return kprintln!("error: invalid address: {}", args[0])
+            },
+            _ => return kprintln!("usage: x/<n> <addr>")
+        };
+
+        let end = count.checked_mul(8).and_then(|n| addr.checked_add(n));
+        if addr == 0 || end.map_or(true, |end| end > IO_BASE) {
+            return kprintln!("error: {:#x} is not in RAM", addr);
+        }
+
+        for i in 0..count {
+            if i % 2 == 0 {
+                kprint!("{:#018x}:", addr + i * 8);
+            }
+            kprint!(" {:016x}", unsafe { *((addr + i * 8) as *const u64) });
+            if i % 2 == 1 || i == count - 1 {
+                kprintln!("");
+            }
+        }
+    }
+}
+
+// Parse a number, hexadecimal with a `0x` prefix and decimal otherwise
+fn parse_number(s: &str) -> Option<u64> {
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse::<u64>().ok()
+    }
+}
+
+/// How a shell runs the commands it reads.
+enum Mode<'a> {
+    /// Commands run as separate processes under job control.
+    Jobs(Jobs),
+    /// Commands run synchronously on the caller's stack, and debugger
+    /// commands act on the trapped state.
+    Debug(Debugger<'a>),
+}
+
+/// Starts a shell using `prefix` as the prefix for each line. Commands run as
+/// separate processes under job control: `cmd &` runs `cmd` in the background,
+/// `jobs` lists jobs and `fg` brings one to the foreground. This function
 /// returns when exit is called.
 pub fn shell(prefix: &str) {
+    run_shell(prefix, Mode::Jobs(Jobs::new()));
+}
+
+/// Starts a shell using `prefix` as the prefix for each line, running every
+/// command synchronously on the caller's stack. This is used from exception
+/// context, where no process can be started. The debugger commands `regs`,
+/// `set <reg> <val>` and `x/<n> <addr>` inspect and modify the trapped state
+/// in `tf`, and `break` and `watch` manage hardware breakpoints and
+/// watchpoints. This function returns when `continue` (or exit) or `step` is
+/// called, which it returns.
+pub fn debug_shell(prefix: &str, tf: &mut TrapFrame) -> Resume {
+    run_shell(prefix, Mode::Debug(Debugger { tf }))
+}
+
+fn run_shell(prefix: &str, mut mode: Mode) -> Resume {
     // Print our awesome welcome message
-    kprintln!("{}", SHELL_WELCOME);
-    kprintln!("{}", "Welcome to Ichigo OS! 僕のダーリング。");
-    kprintln!("");
+    // The debugger is entered too often for it, e.g. on every step
+    match mode {
+        Mode::Debug(_) => (),
+        _ => {
+            kprintln!("{}", SHELL_WELCOME);
+            kprintln!("{}", "Welcome to Ichigo OS! 僕のダーリング。");
+            kprintln!("");
+        }
+    }
     kprint!("{}", prefix);
 
     // Current working directory
@@ -113,7 +549,10 @@ pub fn shell(prefix: &str) {
     let mut line = StackVec::new(&mut line_buf[..]);
     'shell_loop: loop {
         // Wait for the next byte to come in
-        let byte = CONSOLE.lock().read_byte();
+        let byte = match mode {
+            Mode::Jobs(ref mut jobs) => jobs.read_byte(),
+            Mode::Debug(_) => CONSOLE.lock().read_byte()
+        };
 
         if byte == b'\n' || byte == b'\r' {
             // Line break! We hopefully got a command!
@@ -123,6 +562,11 @@ pub fn shell(prefix: &str) {
                 let line_str = str::from_utf8(&line);
 
                 if let Ok(line_str) = line_str {
+                    // A trailing `&` runs the command in the background
+                    let line_str = line_str.trim_right();
+                    let background = line_str.ends_with('&');
+                    let line_str = line_str.trim_right_matches('&');
+
                     let mut cmd_buf = [""; 64];
                     let cmd = Command::parse(line_str, &mut cmd_buf[..]);
                     match cmd {
@@ -130,13 +574,26 @@ pub fn shell(prefix: &str) {
                             if cmd.path() == "exit" {
                                 kprintln!("shell exitting.");
                                 break 'shell_loop;
-                            } else {
-                                process_command(&mut pwd, cmd);
+                            }
+
+                            match mode {
+                                Mode::Jobs(ref mut jobs) => {
+                                    jobs.run(&mut pwd, cmd, line_str, background, false)
+                                },
+                                Mode::Debug(ref mut debugger) => {
+                                    if let Some(resume) = debugger.run(&mut pwd, cmd) {
+                                        return resume;
+                                    }
+                                }
                             }
                         },
                         Err(Error::TooManyArgs) => kprintln!("error: too many arguments"),
                         Err(Error::Empty) => ()
                     }
+
+                    if let Mode::Jobs(ref mut jobs) = mode {
+                        jobs.reap_finished();
+                    }
                     kprint!("{}", prefix);
                 } else {
                     kprintln!("{}", "Illegal character detected.");
@@ -166,6 +623,8 @@ pub fn shell(prefix: &str) {
             CONSOLE.lock().write(&[byte]).unwrap();
         }
     }
+
+    Resume::Continue
 }
 
 // Trait for a Shell command
@@ -175,6 +634,12 @@ trait ShellCmd: Sync + Send {
 
     // Called when the command is invoked via shell
     fn exec(&self, pwd: &mut PathBuf, args: &Command);
+
+    // Built-in commands run inside the shell process itself
+    // instead of being started as a job
+    fn builtin(&self) -> bool {
+        false
+    }
 }
 
 // $ echo a b c
@@ -219,6 +684,36 @@ impl ShellCmd for ExceptionCmd {
     }
 }
 
+// $ gdb <pid>
+// stop process `pid` at its next instruction in the GDB stub
+struct GdbCmd;
+impl ShellCmd for GdbCmd {
+    fn name(&self) -> &'static str {
+        "gdb"
+    }
+
+    fn exec(&self, pwd: &mut PathBuf, args: &Command) {
+        if args.arguments().len() != 1 {
+            return kprintln!("usage: gdb <pid>");
+        }
+
+        let pid = match args.arguments()[0].parse::<Id>() {
+            Ok(pid) => pid,
+            Err(_) => return kprintln!("error: invalid pid: {}", args.arguments()[0])
+        };
+
+        // Nothing else reaches the console once the session is attached
+        kprintln!("waiting for gdb on the console UART");
+        if !gdb::attach(pid) {
+            kprintln!("error: cannot debug process {}", pid);
+        }
+    }
+
+    fn builtin(&self) -> bool {
+        true
+    }
+}
+
 // Read Atags information from the memory
 struct AtagsCmd;
 impl ShellCmd for AtagsCmd {
@@ -322,6 +817,10 @@ impl ShellCmd for CdCmd {
             }
         }
     }
+
+    fn builtin(&self) -> bool {
+        true
+    }
 }
 
 // List the entries in current directory
@@ -481,7 +980,113 @@ impl ShellCmd for SleepCmd {
         }
         let ms = ms.unwrap();
         kprintln!("sleeping for {} ms", ms);
-        let slept = syscall::call_sleep(ms);
+        let slept = ulib::sleep(ms);
         kprintln!("slept {} ms", slept);
     }
-}
\ No newline at end of file
+}
+
+// $ irqstat
+// show how often each interrupt fired and how long its handler took
+struct IrqStatCmd;
+impl ShellCmd for IrqStatCmd {
+    fn name(&self) -> &'static str {
+        "irqstat"
+    }
+
+    fn exec(&self, pwd: &mut PathBuf, args: &Command) {
+        use pi::interrupt::Interrupt;
+        use pi::timer::current_time;
+
+        let uptime_us = ::std::cmp::max(current_time(), 1);
+        kprintln!("{:<14} {:>10} {:>10} {:>10} {:>10}",
+                  "irq", "count", "rate/s", "avg(cyc)", "max(cyc)");
+        for &int in Interrupt::ALL.iter() {
+            let stat = traps::irq_stat(int);
+            // Hundredths of interrupts per second
+            let rate = (stat.count as u128 * 100_000_000 / uptime_us as u128) as u64;
+            kprintln!("{:<14} {:>10} {:>7}.{:02} {:>10} {:>10}", format!("{:?}", int), stat.count,
+                      rate / 100, rate % 100, stat.average_cycles(), stat.max_cycles);
+        }
+
+        let (spurious, unhandled) = traps::irq_counters();
+        kprintln!("spurious: {}, unhandled: {}", spurious, unhandled);
+        kprintln!("handler times in generic counter cycles at {} Hz",
+                  unsafe { aarch64::counter_frequency() });
+    }
+}
+
+// $ syscallbench [n]
+// time `n` (default 100000) `getpid` system calls, first without the FP/SIMD
+// registers in use, as lazy switching leaves them, then with them in use,
+// which saves and restores them on every call like eager switching would
+struct SyscallBenchCmd;
+impl ShellCmd for SyscallBenchCmd {
+    fn name(&self) -> &'static str {
+        "syscallbench"
+    }
+
+    fn exec(&self, pwd: &mut PathBuf, args: &Command) {
+        use pi::timer::current_time;
+
+        let n = match args.arguments().first() {
+            Some(arg) => match arg.parse::<u64>() {
+                Ok(n) if n > 0 => n,
+                _ => return kprintln!("error: invalid count: {}", arg)
+            },
+            None => 100_000
+        };
+
+        let time = |label: &str| {
+            let start = current_time();
+            for _ in 0..n {
+                ulib::getpid();
+            }
+            let ns = (current_time() - start) * 1000 / n;
+            kprintln!("{:<8} {} ns/call", label, ns);
+            ns
+        };
+
+        // Start on a fresh time slice; FP access is off if another process ran
+        ulib::yield_now();
+        let lazy = time("lazy");
+        unsafe {
+            asm!("fmov d0, xzr" ::: "v0" : "volatile");
+        }
+        let eager = time("eager");
+        kprintln!("saved    {} ns/call", eager as i64 - lazy as i64);
+    }
+}
+
+// $ strace [off] <pid>
+// turn system call tracing of process `pid` on or off
+struct StraceCmd;
+impl ShellCmd for StraceCmd {
+    fn name(&self) -> &'static str {
+        "strace"
+    }
+
+    fn exec(&self, pwd: &mut PathBuf, args: &Command) {
+        let args = args.arguments();
+        let (traced, pid) = match args.len() {
+            _ if args.first() == Some(&"run") => {
+                return kprintln!("error: `strace run` needs a shell with job control");
+            },
+            1 => (true, args[0]),
+            2 if args[0] == "off" => (false, args[1]),
+            _ => return kprintln!("usage: strace [off] <pid> | strace run <command>")
+        };
+
+        let pid = match pid.parse::<Id>() {
+            Ok(pid) => pid,
+            Err(_) => return kprintln!("error: invalid pid: {}", pid)
+        };
+
+        if SCHEDULER.with_process(pid, |p| p.traced = traced).is_none() {
+            kprintln!("error: no such process: {}", pid);
+        }
+    }
+
+    fn builtin(&self) -> bool {
+        true
+    }
+}
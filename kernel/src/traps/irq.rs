@@ -1,14 +1,209 @@
-use pi::timer;
-use pi::interrupt::{Controller, Interrupt};
+use pi::interrupt::{Controller, Interrupt, BASIC_ARM_INTERRUPTS};
 
-use console::kprintln;
+use aarch64;
+use mutex::Mutex;
 use traps::TrapFrame;
-use process::{TICK, State};
 use SCHEDULER;
 
-pub fn handle_irq(interrupt: Interrupt, tf: &mut TrapFrame) {
-    if interrupt == Interrupt::Timer1 {
-        SCHEDULER.switch(State::Ready, tf).expect("Fatal: no process running");
-        timer::tick_in(TICK);
+/// A handler for an interrupt. It runs in the exception context, with the
+/// trap frame of the interrupted code, and must clear the interrupt at its
+/// source.
+pub type IrqHandler = fn(Interrupt, &mut TrapFrame);
+
+/// How often an interrupt fired and how long its handler took, in cycles of
+/// the generic counter (`CNTPCT_EL0`).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct IrqStat {
+    /// The number of times the interrupt was pending when an IRQ was taken.
+    pub count: u64,
+    /// The total time spent in the handler, in cycles.
+    pub total_cycles: u64,
+    /// The longest time spent in the handler, in cycles.
+    pub max_cycles: u64,
+}
+
+impl IrqStat {
+    const ZERO: IrqStat = IrqStat { count: 0, total_cycles: 0, max_cycles: 0 };
+
+    /// The average time spent in the handler, in cycles.
+    pub fn average_cycles(&self) -> u64 {
+        match self.count {
+            0 => 0,
+            count => self.total_cycles / count
+        }
+    }
+}
+
+/// The registered interrupt handlers and statistics of every interrupt,
+/// indexed by interrupt number, and the counts of interrupts no handler took
+/// care of.
+pub struct Irqs {
+    handlers: [Option<IrqHandler>; Interrupt::MAX],
+    stats: [IrqStat; Interrupt::MAX],
+    spurious: u64,
+    unhandled: u64,
+}
+
+impl Irqs {
+    /// Returns a table without any handlers.
+    pub const fn new() -> Irqs {
+        Irqs {
+            handlers: [None; Interrupt::MAX],
+            stats: [IrqStat::ZERO; Interrupt::MAX],
+            spurious: 0,
+            unhandled: 0
+        }
+    }
+
+    /// Registers `handler` for `int`. Returns `false` if `int` already has a
+    /// handler.
+    pub fn register(&mut self, int: Interrupt, handler: IrqHandler) -> bool {
+        let slot = &mut self.handlers[int as usize];
+        if slot.is_some() {
+            return false;
+        }
+
+        *slot = Some(handler);
+        true
+    }
+
+    /// Removes the handler of `int` and returns it, if any.
+    pub fn unregister(&mut self, int: Interrupt) -> Option<IrqHandler> {
+        self.handlers[int as usize].take()
+    }
+
+    /// Returns the handler of `int`, if any.
+    pub fn handler(&self, int: Interrupt) -> Option<IrqHandler> {
+        self.handlers[int as usize]
     }
+
+    /// Accounts for an IRQ taken with the GPU interrupts in `pending` (bit
+    /// `n` for interrupt `n`) and the ARM-specific interrupts in `basic`
+    /// pending, and returns the handlers to run. An IRQ without any pending
+    /// interrupt is spurious; every pending interrupt without a handler is
+    /// unhandled.
+    pub fn dispatch(&mut self, pending: u64, basic: u32) -> [Option<IrqHandler>; Interrupt::MAX] {
+        let pending = known(pending, basic);
+        if pending == 0 {
+            self.spurious += 1;
+        }
+
+        for &int in Interrupt::ALL.iter() {
+            if !is_pending(pending, int) {
+                continue;
+            }
+
+            self.stats[int as usize].count += 1;
+            if self.handler(int).is_none() {
+                self.unhandled += 1;
+            }
+        }
+
+        self.handlers
+    }
+
+    /// Records that the handler of `int` ran for `cycles` cycles.
+    pub fn record(&mut self, int: Interrupt, cycles: u64) {
+        let stat = &mut self.stats[int as usize];
+        stat.total_cycles = stat.total_cycles.saturating_add(cycles);
+        stat.max_cycles = ::std::cmp::max(stat.max_cycles, cycles);
+    }
+
+    /// Returns the statistics of `int`.
+    pub fn stat(&self, int: Interrupt) -> IrqStat {
+        self.stats[int as usize]
+    }
+
+    /// The number of IRQs taken without any pending interrupt.
+    pub fn spurious(&self) -> u64 {
+        self.spurious
+    }
+
+    /// The number of pending interrupts that had no handler.
+    pub fn unhandled(&self) -> u64 {
+        self.unhandled
+    }
+}
+
+/// Returns the pending interrupts as one set, bit `n` for interrupt `n`:
+/// the GPU interrupts in `pending` and the ARM-specific ones in `basic`. Only
+/// bits that belong to an `Interrupt` are kept; others cannot have been
+/// enabled through `Controller::enable()`.
+fn known(pending: u64, basic: u32) -> u128 {
+    let basic = (basic & BASIC_ARM_INTERRUPTS) as u128;
+    let pending = pending as u128 | basic << Interrupt::BASIC;
+    Interrupt::ALL.iter().fold(0, |mask, &int| mask | (1 << int as u128)) & pending
+}
+
+fn is_pending(pending: u128, int: Interrupt) -> bool {
+    pending & (1 << int as u128) != 0
+}
+
+static IRQS: Mutex<Irqs> = Mutex::new(Irqs::new());
+
+/// Registers `handler` for `int` and enables the interrupt. Returns `false`
+/// if `int` already has a handler.
+pub fn register_irq_handler(int: Interrupt, handler: IrqHandler) -> bool {
+    if !IRQS.lock().register(int, handler) {
+        return false;
+    }
+
+    Controller::new().enable(int);
+    true
+}
+
+/// Disables `int` and removes its handler, which is returned.
+pub fn unregister_irq_handler(int: Interrupt) -> Option<IrqHandler> {
+    Controller::new().disable(int);
+    IRQS.lock().unregister(int)
+}
+
+/// Returns the number of spurious IRQs and of pending interrupts that had no
+/// handler.
+pub fn irq_counters() -> (u64, u64) {
+    let irqs = IRQS.lock();
+    (irqs.spurious(), irqs.unhandled())
+}
+
+/// Returns the statistics of `int`.
+pub fn irq_stat(int: Interrupt) -> IrqStat {
+    IRQS.lock().stat(int)
+}
+
+/// Runs the handlers of all pending interrupts, in order of interrupt
+/// number, timing them in cycles of the generic counter. Time a handler
+/// spends idling in the scheduler, waiting for a process to become ready, is
+/// not counted. The lock on the handler table is released before they run, so
+/// they may switch processes and (un)register handlers. An interrupt without a handler is disabled, since
+/// nothing would clear it.
+pub fn handle_irq(tf: &mut TrapFrame) {
+    let mut controller = Controller::new();
+    let basic = controller.basic_pending();
+    let gpu = controller.pending();
+    let handlers = IRQS.lock().dispatch(gpu, basic);
+    let pending = known(gpu, basic);
+
+    for &int in Interrupt::ALL.iter() {
+        if !is_pending(pending, int) {
+            continue;
+        }
+
+        match handlers[int as usize] {
+            Some(handler) => {
+                let idle = SCHEDULER.idle_time();
+                let start = unsafe { aarch64::counter() };
+                handler(int, tf);
+                let cycles = unsafe { aarch64::counter() } - start;
+                let idle = us_to_cycles(SCHEDULER.idle_time() - idle);
+                IRQS.lock().record(int, cycles.saturating_sub(idle));
+            },
+            None => controller.disable(int)
+        }
+    }
+}
+
+/// Converts microseconds to cycles of the generic counter.
+fn us_to_cycles(us: u64) -> u64 {
+    let frequency = unsafe { aarch64::counter_frequency() };
+    (us as u128 * frequency as u128 / 1_000_000) as u64
 }
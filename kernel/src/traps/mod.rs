@@ -1,19 +1,41 @@
 mod irq;
 mod trap_frame;
 mod syndrome;
+mod strace;
+mod report;
 pub mod syscall;
 
-use pi::interrupt::{Controller, Interrupt};
+#[cfg(test)]
+mod tests;
 
 pub use self::trap_frame::TrapFrame;
+pub use self::syndrome::{Syndrome, Fault, DataAccess};
+pub use self::report::{dump_registers, faulting_sp};
+pub use self::irq::{IrqHandler, IrqStat, register_irq_handler, unregister_irq_handler};
+pub use self::irq::{irq_counters, irq_stat};
 
-use console::kprintln;
 use aarch64;
+use backtrace;
+use gdb;
 use shell;
-use self::syndrome::Syndrome;
+use shell::Resume;
+use aarch64::debug;
+use mutex::Mutex;
+use console::kprintln;
+use process::{State, ExitStatus, Id};
+use SCHEDULER;
 use self::irq::handle_irq;
 use self::syscall::handle_syscall;
 
+/// While single stepping over the instruction that hit a hardware breakpoint
+/// or watchpoint, with all of them suspended, the code being stepped (see
+/// `stepped()`) and how the debugger asked to resume it.
+static STEP_OVER: Mutex<Option<(Option<Id>, Resume)>> = Mutex::new(None);
+
+/// `PSTATE.D` of the kernel code being single stepped, which has to run with
+/// debug exceptions unmasked while it is stepped, to restore once it is not.
+static KERNEL_STEP_MASK: Mutex<Option<u64>> = Mutex::new(None);
+
 #[repr(u16)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Kind {
@@ -39,28 +61,208 @@ pub struct Info {
     kind: Kind,
 }
 
+impl Info {
+    /// Returns the description of an exception of `kind` taken from `source`.
+    pub fn new(source: Source, kind: Kind) -> Info {
+        Info { source, kind }
+    }
+
+    /// Whether the exception was taken from a lower exception level.
+    pub fn is_lower(&self) -> bool {
+        match self.source {
+            Source::LowerAArch64 | Source::LowerAArch32 => true,
+            _ => false
+        }
+    }
+}
+
 /// This function is called when an exception occurs. The `info` parameter
 /// specifies the source and kind of exception that has occurred. The `esr` is
 /// the value of the exception syndrome register. Finally, `tf` is a pointer to
 /// the trap frame for the exception.
+///
+/// Interrupts are dispatched to their registered handlers. System calls are
+/// dispatched to their handlers. The first FP/SIMD use of a process after it
+/// is switched in traps and loads its FP/SIMD registers. A breakpoint (`brk`) opens
+/// the debug shell and resumes after the breakpoint, as do hits of hardware
+/// breakpoints and watchpoints and completed steps. Any other exception is
+/// reported; a user process that caused it is terminated and the next process
+/// is scheduled, while one caused by the kernel is fatal.
 #[no_mangle]
 pub extern fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
     if info.kind == Kind::Irq {
-        if Controller::new().is_pending(Interrupt::Timer1) {
-            handle_irq(Interrupt::Timer1, tf);
-            return;
-        }
+        return handle_irq(tf);
     }
     let exception_syndrome = Syndrome::from(esr);
 
-    if let Syndrome::Svc(num) = exception_syndrome {
-        handle_syscall(num, tf);
-    } else if exception_syndrome != Syndrome::WfiWfe {
-        kprintln!("---- Exception ----");
-        kprintln!("info: {:?}", info);
-        kprintln!("syndrome: {:?}", exception_syndrome);
-        kprintln!("-------------------");
-        shell::shell("debug> "); // Start debug shell
+    match exception_syndrome {
+        // `ELR` already points past the `svc`
+        Syndrome::Svc(num) => handle_syscall(num, tf),
+        // `ELR` points at the trapped instruction itself
+        Syndrome::WfiWfe => tf.program_counter += 4,
+        // The first FP/SIMD use since the process was switched in: load its
+        // registers and retry the instruction with access enabled
+        Syndrome::SimdFp if info.source == Source::LowerAArch64 => {
+            SCHEDULER.with_current(|p| {
+                tf.floating_point_registers = p.trap_frame.floating_point_registers;
+            });
+            unsafe { aarch64::set_user_fp(true) };
+        },
+        Syndrome::Brk(_) => {
+            // Stop at breakpoints GDB placed; it removes them before resuming
+            if !gdb::is_breakpoint(tf.elr()) {
+                if !gdb::attached() {
+                    load_user_fp(info, tf);
+                    report::report(info, esr, 0, tf);
+                }
+                tf.program_counter += 4; // Resume after the breakpoint
+            }
+            enter_debugger(info, tf, false);
+        },
+        // `ELR` points at the instruction that hit, which has not executed
+        Syndrome::Breakpoint => {
+            if !gdb::attached() {
+                kprintln!("breakpoint: pc = {:#x}", tf.elr());
+            }
+            enter_debugger(info, tf, true);
+        },
+        Syndrome::Watchpoint => {
+            if !gdb::attached() {
+                kprintln!("watchpoint: pc = {:#x}, address = {:#x}", tf.elr(),
+                          unsafe { aarch64::far() });
+            }
+            enter_debugger(info, tf, true);
+        },
+        // Returned to the debugger after single stepping
+        Syndrome::Step => {
+            let owner = stepped(info);
+            let step_over = {
+                let mut step_over = STEP_OVER.lock();
+                match *step_over {
+                    Some((stepping, resume)) if stepping == owner => {
+                        *step_over = None;
+                        Some(resume)
+                    },
+                    _ => None
+                }
+            };
+            if step_over.is_some() {
+                unsafe { debug::resume_all(); }
+            }
+
+            match step_over {
+                Some(Resume::Continue) => unsafe { stop_step(tf, info.is_lower()) },
+                _ => {
+                    if !gdb::attached() {
+                        kprintln!("step: pc = {:#x}", tf.elr());
+                    }
+                    enter_debugger(info, tf, false);
+                }
+            }
+        },
+        _ => {
+            load_user_fp(info, tf);
+            report::report(info, esr, unsafe { aarch64::far() }, tf);
+            match info.source {
+                Source::LowerAArch64 | Source::LowerAArch32 => {
+                    kprintln!("process {} terminated: {:?}", tf.thread_id, exception_syndrome);
+                    let status = ExitStatus::Faulted(esr);
+                    SCHEDULER.switch(State::Zombie(status), tf).unwrap();
+                },
+                _ => {
+                    backtrace::print_trapped(tf);
+                    panic!("unhandled exception in kernel: {:?}", exception_syndrome)
+                }
+            }
+        }
+    }
+}
+
+/// Stops the code interrupted by an IRQ, whose state is in `tf`, in the
+/// debugger, as if it had hit a breakpoint.
+pub fn break_in(tf: &mut TrapFrame) {
+    let source = match tf.spsr() & 0b11111 {
+        0b00000 => Source::LowerAArch64,
+        0b00100 => Source::CurrentSpEl0,
+        mode if mode & 0b10000 != 0 => Source::LowerAArch32,
+        _ => Source::CurrentSpElx
+    };
+    enter_debugger(Info::new(source, Kind::Irq), tf, false);
+}
+
+/// Loads the current process's own copy of its FP/SIMD registers into `tf`
+/// if it trapped without access to them. `context_save` then did not store
+/// them, so those in `tf` are stale, but reports and debuggers show them.
+/// They are not restored on return while access is disabled.
+fn load_user_fp(info: Info, tf: &mut TrapFrame) {
+    if info.is_lower() && !unsafe { aarch64::user_fp_enabled() } {
+        SCHEDULER.with_current(|p| {
+            tf.floating_point_registers = p.trap_frame.floating_point_registers;
+        });
+    }
+}
+
+/// Starts the debug shell on the state in `tf`, or stops in the GDB stub if a
+/// session is attached, and prepares to resume the way the debugger asks.
+///
+/// If `hit` is set, the exception is a hit of a hardware breakpoint or
+/// watchpoint and resuming would hit it again. The instruction is then
+/// stepped over with all of them suspended.
+fn enter_debugger(info: Info, tf: &mut TrapFrame, hit: bool) {
+    load_user_fp(info, tf);
+    let resume = if gdb::attached() {
+        gdb::stop(info, tf)
+    } else {
+        shell::debug_shell("debug> ", tf)
+    };
+    let lower = info.is_lower();
+
+    unsafe {
+        if hit {
+            debug::suspend_all();
+            *STEP_OVER.lock() = Some((stepped(info), resume));
+            return start_step(tf, lower);
+        }
+
+        match resume {
+            Resume::Step => start_step(tf, lower),
+            Resume::Continue => stop_step(tf, lower)
+        }
+    }
+}
+
+/// Returns the code a debug exception described by `info` was taken from:
+/// the current process for a lower exception level, `None` for the kernel.
+fn stepped(info: Info) -> Option<Id> {
+    if info.is_lower() { SCHEDULER.current() } else { None }
+}
+
+/// Single steps the code trapped in `tf`, from a lower exception level if
+/// `lower` is set and from the kernel otherwise: the next instruction
+/// executes, then a step exception returns to the debugger.
+unsafe fn start_step(tf: &mut TrapFrame, lower: bool) {
+    if lower {
+        return debug::enable_step(&mut tf.program_state);
+    }
+
+    {
+        let mut mask = KERNEL_STEP_MASK.lock();
+        if mask.is_none() {
+            *mask = Some(tf.spsr() & debug::SPSR_D);
+        }
+    }
+    debug::enable_kernel_step(&mut tf.program_state);
+}
+
+/// Stops single stepping the code trapped in `tf`, masking debug exceptions
+/// again in kernel code that had them masked.
+unsafe fn stop_step(tf: &mut TrapFrame, lower: bool) {
+    debug::disable_step(&mut tf.program_state);
+    if lower {
+        return;
+    }
+
+    if let Some(mask) = KERNEL_STEP_MASK.lock().take() {
+        tf.set_spsr(tf.spsr() | mask);
     }
-    tf.program_counter += 4; // Jump to the next instruction
 }
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use console::{kprint, kprintln};
+use pi::common::IO_BASE;
+use traps::{Info, Source, TrapFrame};
+use traps::syndrome::Syndrome;
+
+/// A saved program status register, displayed decoded into its condition
+/// flags, interrupt masks and mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Spsr(pub u64);
+
+impl Spsr {
+    /// Returns the name of the exception level and stack pointer the saved
+    /// state belongs to, e.g. `EL0t` or `EL1h`.
+    pub fn mode(&self) -> &'static str {
+        if self.0 & (1 << 4) != 0 {
+            return "AArch32";
+        }
+
+        match self.0 & 0b1111 {
+            0b0000 => "EL0t",
+            0b0100 => "EL1t",
+            0b0101 => "EL1h",
+            0b1000 => "EL2t",
+            0b1001 => "EL2h",
+            0b1100 => "EL3t",
+            0b1101 => "EL3h",
+            _ => "invalid"
+        }
+    }
+}
+
+impl fmt::Display for Spsr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag = |bit: u64, name: char| if self.0 & (1 << bit) != 0 { name } else { '-' };
+        write!(f, "{:#010x} [{}{}{}{} {}{}{}{}",
+               self.0,
+               flag(31, 'N'), flag(30, 'Z'), flag(29, 'C'), flag(28, 'V'),
+               flag(9, 'D'), flag(8, 'A'), flag(7, 'I'), flag(6, 'F'))?;
+        if self.0 & (1 << 21) != 0 {
+            write!(f, " SS")?;
+        }
+        if self.0 & (1 << 20) != 0 {
+            write!(f, " IL")?;
+        }
+        write!(f, " {}]", self.mode())
+    }
+}
+
+/// The number of bytes of stack dumped below and above the stack pointer.
+const STACK_BELOW: usize = 64;
+const STACK_ABOVE: usize = 256;
+
+/// Returns `true` if the syndrome sets `FAR_EL1` to the faulting address.
+fn far_valid(syndrome: Syndrome) -> bool {
+    match syndrome {
+        Syndrome::InstructionAbort { far_valid, .. } |
+        Syndrome::DataAbort { far_valid, .. } => far_valid,
+        Syndrome::PCAlignmentFault | Syndrome::Watchpoint => true,
+        _ => false
+    }
+}
+
+/// Returns the stack pointer of the code that took the exception described
+/// by `info`, whose state is in `tf`.
+pub fn faulting_sp(info: Info, tf: &TrapFrame) -> u64 {
+    match info.source {
+        // The exception was taken on the stack that was in use; the trap
+        // frame sits right below where the stack pointer was
+        Source::CurrentSpElx => tf as *const TrapFrame as u64 + TrapFrame::SIZE as u64,
+        _ => tf.stack_pointer
+    }
+}
+
+/// Prints the stack around `sp`, 16 bytes per line. Nothing is read unless
+/// the whole range lies in RAM.
+fn dump_stack(sp: u64) {
+    let start = (sp as usize).saturating_sub(STACK_BELOW) & !0xf;
+    let end = (sp as usize).saturating_add(STACK_ABOVE);
+    if start == 0 || end > IO_BASE {
+        return kprintln!("stack: {:#x} is not in RAM", sp);
+    }
+
+    kprintln!("stack:");
+    let mut line = start;
+    while line < end {
+        let words = unsafe { *(line as *const [u64; 2]) };
+        let marker = if sp as usize >= line && (sp as usize) < line + 16 { "<- sp" } else { "" };
+        kprintln!("  {:#018x}: {:016x} {:016x} {}", line, words[0], words[1], marker);
+        line += 16;
+    }
+}
+
+/// Prints a complete report of an unexpected exception: where it came from,
+/// its syndrome, the faulting address, the process it interrupted and all of
+/// that process's registers and stack.
+pub fn report(info: Info, esr: u32, far: u64, tf: &TrapFrame) {
+    let syndrome = Syndrome::from(esr);
+    kprintln!("---- Exception ----");
+    kprintln!("info: {:?}", info);
+    kprintln!("syndrome: {:?} (ESR {:#010x})", syndrome, esr);
+    if far_valid(syndrome) {
+        kprintln!("FAR:  {:#018x}", far);
+    }
+    kprintln!("ELR:  {:#018x}", tf.elr());
+    kprintln!("SPSR: {}", Spsr(tf.spsr()));
+    kprintln!("SP:   {:#018x}", faulting_sp(info, tf));
+    kprintln!("pid:  {}", tf.thread_id);
+    dump_registers(tf);
+    dump_stack(faulting_sp(info, tf));
+    kprintln!("-------------------");
+}
+
+/// Prints all general purpose and SIMD/floating point registers in `tf`.
+pub fn dump_registers(tf: &TrapFrame) {
+    // Print without allocating: the heap may be what faulted
+    for n in 0..31 {
+        kprint!("x{:<2} {:016x}", n, tf.x(n));
+        if n % 4 == 3 || n == 30 {
+            kprintln!("");
+        } else {
+            kprint!("  ");
+        }
+    }
+
+    for row in 0..16 {
+        kprintln!("q{:<2} {:032x}  q{:<2} {:032x}", 2 * row, tf.q(2 * row), 2 * row + 1, tf.q(2 * row + 1));
+    }
+}
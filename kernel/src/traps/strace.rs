@@ -0,0 +1,113 @@
+use std::fmt;
+use std::mem;
+
+use console::kprintln;
+use pi::timer;
+use process::{Id, Process, State};
+use traps::TrapFrame;
+use traps::syscall::{self, Arg, Args, Error, Syscall};
+use SCHEDULER;
+
+/// A system call with its number and arguments, shown as
+/// `name#num(arg, ...)`.
+#[derive(Copy, Clone)]
+struct Call {
+    num: u16,
+    syscall: &'static Syscall,
+    args: Args,
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}(", self.syscall.name, self.num)?;
+        for (i, (arg, value)) in self.syscall.args.iter().zip(self.args.iter()).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            match *arg {
+                Arg::Int => write!(f, "{}", value)?,
+                Arg::Ptr => write!(f, "{:#x}", value)?
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// The result of a system call, shown as `= value` or `= Error`.
+struct Outcome(Result<u64, Error>);
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Ok(value) => write!(f, "= {}", value),
+            Err(error) => write!(f, "= {:?}", error)
+        }
+    }
+}
+
+/// Handles system call `num`, `syscall`, made by the traced process `pid`,
+/// whose state is in `tf`, logging the call, its result and how long it took.
+/// `pid` must be the current process.
+///
+/// A call that blocks is logged twice: once when the process blocks, and
+/// again with the result once the event it waits for arrives.
+pub fn trace(pid: Id, num: u16, syscall: &'static Syscall, tf: &mut TrapFrame) {
+    let call = Call { num, syscall, args: syscall::args(tf) };
+    let start = timer::current_time();
+    (syscall.handler)(&call.args, tf);
+
+    if SCHEDULER.current() == Some(pid) {
+        // The call completed without switching to another process
+        let elapsed = timer::current_time() - start;
+        return kprintln!("[{}] {} {} <{} us>", pid, call, Outcome(syscall::result(tf)), elapsed);
+    }
+
+    // The call switched to another process: it blocked, yielded or ended
+    // the process
+    let switched = SCHEDULER.with_process(pid, |p| {
+        match mem::replace(&mut p.state, State::Ready) {
+            State::Waiting(mut poll) => {
+                p.state = State::Waiting(Box::new(move |p: &mut Process| {
+                    if !poll(p) {
+                        return false;
+                    }
+
+                    let elapsed = timer::current_time() - start;
+                    let outcome = Outcome(syscall::result(&p.trap_frame));
+                    kprintln!("[{}] <... {}#{} resumed> {} <{} us>",
+                              pid, call.syscall.name, call.num, outcome, elapsed);
+                    true
+                }));
+                Switched::Blocked
+            },
+            State::Zombie(status) => {
+                p.state = State::Zombie(status);
+                Switched::Terminated
+            },
+            state => {
+                p.state = state;
+                Switched::Returned(Outcome(syscall::result(&p.trap_frame)))
+            }
+        }
+    });
+
+    match switched {
+        Some(Switched::Blocked) => kprintln!("[{}] {} <unfinished ...>", pid, call),
+        Some(Switched::Returned(outcome)) => {
+            let elapsed = timer::current_time() - start;
+            kprintln!("[{}] {} {} <{} us>", pid, call, outcome, elapsed)
+        },
+        Some(Switched::Terminated) | None => kprintln!("[{}] {} = ?", pid, call)
+    }
+}
+
+/// What became of a traced process whose system call switched away from it.
+enum Switched {
+    /// The process waits for the call to complete.
+    Blocked,
+    /// The call completed, e.g. by yielding, with this result.
+    Returned(Outcome),
+    /// The process terminated.
+    Terminated,
+}
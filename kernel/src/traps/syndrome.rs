@@ -1,3 +1,5 @@
+/// The kind of an instruction or data abort, decoded from the fault status
+/// code (`IFSC`/`DFSC`, ISS bits 5:0) (ref: D12.2.28).
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Fault {
     AddressSize,
@@ -6,31 +8,99 @@ pub enum Fault {
     Permission,
     Alignment,
     TlbConflict,
+    /// A synchronous external abort, not on a translation table walk.
+    SyncExternal,
+    /// A synchronous external abort on a translation table walk.
+    SyncExternalOnWalk,
+    /// A synchronous parity or ECC error, not on a translation table walk.
+    SyncParity,
+    /// A synchronous parity or ECC error on a translation table walk.
+    SyncParityOnWalk,
     Other(u8)
 }
 
 impl Fault {
-    fn parse_level(val: u32) -> u8 {
-        (val & 0x3) as u8
+    /// Returns the translation table level of the fault in `val` if its kind
+    /// reports one: address size, translation, access flag, permission and
+    /// faults on a table walk.
+    fn parse_level(val: u32) -> Option<u8> {
+        use self::Fault::*;
+        match Fault::from(val) {
+            AddressSize | Translation | AccessFlag | Permission
+                | SyncExternalOnWalk | SyncParityOnWalk => Some((val & 0x3) as u8),
+            _ => None
+        }
     }
 }
 
 impl From<u32> for Fault {
     fn from(val: u32) -> Fault {
         use self::Fault::*;
-        let f = ((val & 0x3C) >> 2) as u8; // The 2-6 bits of the ISS are the fault type
-        match f {
+        let fsc = (val & 0x3F) as u8; // The low 6 bits of the ISS are the status code
+        let level = fsc & 0b11;
+        match fsc >> 2 {
+            // Bits 1:0 hold the level
             0b0000 => AddressSize,
             0b0001 => Translation,
-            0b0010 => AccessFlag,
-            0b0011 => Permission,
-            0b1100 => TlbConflict,
-            0b1000 => Alignment,
-            _ => Other(f)
+            0b0010 if level != 0 => AccessFlag,
+            0b0011 if level != 0 => Permission,
+            0b0101 => SyncExternalOnWalk,
+            0b0111 => SyncParityOnWalk,
+            _ => match fsc {
+                0b010000 => SyncExternal,
+                0b011000 => SyncParity,
+                0b100001 => Alignment,
+                0b110000 => TlbConflict,
+                _ => Other(fsc)
+            }
         }
     }
 }
 
+/// The access that caused a data abort, decoded when the syndrome is valid
+/// (`ISV`, ISS bit 24), i.e. for single register loads and stores.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DataAccess {
+    /// The size of the access in bytes (`SAS`): 1, 2, 4 or 8.
+    pub size: u8,
+    /// Whether the loaded value is sign extended (`SSE`).
+    pub sign_extend: bool,
+    /// The register transferred (`SRT`); 31 is the zero register.
+    pub register: u8,
+    /// Whether the register is 64-bit (`SF`) rather than 32-bit.
+    pub sixty_four: bool,
+    /// Whether the access has acquire/release semantics (`AR`).
+    pub acquire_release: bool,
+}
+
+impl DataAccess {
+    /// Decodes the access in a data abort ISS, if the syndrome is valid.
+    fn from_iss(iss: u32) -> Option<DataAccess> {
+        if iss & (1 << 24) == 0 {
+            return None;
+        }
+
+        Some(DataAccess {
+            size: 1 << ((iss >> 22) & 0b11),
+            sign_extend: iss & (1 << 21) != 0,
+            register: ((iss >> 16) & 0x1F) as u8,
+            sixty_four: iss & (1 << 15) != 0,
+            acquire_release: iss & (1 << 14) != 0,
+        })
+    }
+}
+
+/// `FnV`, ISS bit 10: `FAR_EL1` does not hold the faulting address.
+const ISS_FNV: u32 = 1 << 10;
+/// `EA`, ISS bit 9: the implementation defined external abort type.
+const ISS_EA: u32 = 1 << 9;
+/// `CM`, ISS bit 8: the fault came from a cache maintenance instruction.
+const ISS_CM: u32 = 1 << 8;
+/// `S1PTW`, ISS bit 7: a stage 2 fault on a stage 1 translation table walk.
+const ISS_S1PTW: u32 = 1 << 7;
+/// `WnR`, ISS bit 6: the fault came from a write rather than a read.
+const ISS_WNR: u32 = 1 << 6;
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Syndrome {
     Unknown,
@@ -48,12 +118,32 @@ pub enum Syndrome {
     MsrMrsSystem,
     InstructionAbort {
         kind: Fault,
-        level: u8,
+        /// The translation table level, if `kind` reports one.
+        level: Option<u8>,
+        /// Whether `FAR_EL1` holds the faulting address (`FnV` clear).
+        far_valid: bool,
+        /// The external abort type (`EA`).
+        external: bool,
+        /// Whether the fault was on a stage 1 table walk (`S1PTW`).
+        s1ptw: bool,
     },
     PCAlignmentFault,
     DataAbort {
         kind: Fault,
-        level: u8
+        /// The translation table level, if `kind` reports one.
+        level: Option<u8>,
+        /// Whether the fault came from a write (`WnR`).
+        write: bool,
+        /// The faulting access, if the syndrome describes it (`ISV`).
+        access: Option<DataAccess>,
+        /// Whether `FAR_EL1` holds the faulting address (`FnV` clear).
+        far_valid: bool,
+        /// The external abort type (`EA`).
+        external: bool,
+        /// Whether the fault came from a cache maintenance instruction (`CM`).
+        cache_maintenance: bool,
+        /// Whether the fault was on a stage 1 table walk (`S1PTW`).
+        s1ptw: bool,
     },
     SpAlignmentFault,
     TrappedFpu,
@@ -70,7 +160,7 @@ impl From<u32> for Syndrome {
     fn from(esr: u32) -> Syndrome {
         use self::Syndrome::*;
         let ec = esr >> 26; // The high 6 bits of ESR is the Exception Class
-        let iss = esr & 0xFFFFFF; // The low 24 bits of ESR is the ISS
+        let iss = esr & 0x1FFFFFF; // The low 25 bits of ESR is the ISS
 
         match ec {
             0b000000 => Unknown,
@@ -88,12 +178,21 @@ impl From<u32> for Syndrome {
             0b011000 => MsrMrsSystem,
             0b100000 | 0b100001 => InstructionAbort {
                 kind: Fault::from(iss),
-                level: Fault::parse_level(iss)
+                level: Fault::parse_level(iss),
+                far_valid: iss & ISS_FNV == 0,
+                external: iss & ISS_EA != 0,
+                s1ptw: iss & ISS_S1PTW != 0,
             },
             0b100010 => PCAlignmentFault,
             0b100100 | 0b100101 => DataAbort {
                 kind: Fault::from(iss),
-                level: Fault::parse_level(iss)
+                level: Fault::parse_level(iss),
+                write: iss & ISS_WNR != 0,
+                access: DataAccess::from_iss(iss),
+                far_valid: iss & ISS_FNV == 0,
+                external: iss & ISS_EA != 0,
+                cache_maintenance: iss & ISS_CM != 0,
+                s1ptw: iss & ISS_S1PTW != 0,
             },
             0b100110 => SpAlignmentFault,
             0b101000 | 0b101100 => TrappedFpu,
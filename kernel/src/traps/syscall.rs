@@ -1,8 +1,201 @@
+use std::io;
+use std::cmp::min;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+
 use traps::TrapFrame;
+use traps::strace;
 use console::kprintln;
-use SCHEDULER;
+use {SCHEDULER, FUTEXES};
 use pi::timer;
 use process;
+use process::{Descriptor, Fd, RealTimeError};
+
+/// The number of arguments a system call can take, passed in `x0`-`x5`.
+pub const MAX_ARGS: usize = 6;
+
+/// The largest number of bytes `read` and `write` move in one call.
+pub const MAX_COPY: usize = 4096;
+
+/// The arguments of a system call.
+pub type Args = [u64; MAX_ARGS];
+
+// The status codes and clock IDs are part of the system call ABI, which
+// `ulib` defines for both sides
+pub use ulib::{Error, Clock};
+
+/// Returns the status code for an I/O error of a kernel object.
+fn io_error(error: io::Error) -> Error {
+    match error.kind() {
+        io::ErrorKind::WouldBlock => Error::Again,
+        io::ErrorKind::BrokenPipe => Error::BrokenPipe,
+        io::ErrorKind::InvalidInput => Error::Invalid,
+        _ => Error::Io
+    }
+}
+
+/// Returns the arguments of a system call from `x0`-`x5` in `tf`.
+pub fn args(tf: &TrapFrame) -> Args {
+    [tf.x(0), tf.x(1), tf.x(2), tf.x(3), tf.x(4), tf.x(5)]
+}
+
+/// Stores the result of a system call into `tf`: the return value in `x0` and
+/// the status in `x7`.
+pub fn set_result(tf: &mut TrapFrame, result: Result<u64, Error>) {
+    let (value, status) = match result {
+        Ok(value) => (value, 0),
+        Err(error) => (0, error as u64)
+    };
+    tf.set_x(0, value);
+    tf.set_x(7, status);
+}
+
+/// Returns the result stored into `tf` by `set_result()`.
+pub fn result(tf: &TrapFrame) -> Result<u64, Error> {
+    let (value, status) = (tf.x(0), tf.x(7));
+    Error::from_status(status).map(|_| value)
+}
+
+/// Runs the non-blocking operation `op` on behalf of the current process and
+/// stores its result into the trap frame. If `op` would block, failing with
+/// `Error::Again`, the process is put into the waiting state and `op` is
+/// retried each time the scheduler polls it, until it completes or fails.
+/// `op` is always passed the process it runs for.
+fn block_on<F>(mut op: F, tf: &mut TrapFrame)
+    where F: FnMut(&mut process::Process) -> Result<u64, Error> + Send + 'static
+{
+    match SCHEDULER.with_current(|p| op(p)) {
+        Some(Err(Error::Again)) => (),
+        Some(result) => return set_result(tf, result),
+        None => return set_result(tf, Err(Error::Io))
+    }
+
+    let f = Box::new(move |p: &mut process::Process| {
+        match op(p) {
+            Err(Error::Again) => false,
+            result => {
+                set_result(&mut p.trap_frame, result);
+                true
+            }
+        }
+    });
+    SCHEDULER.switch(process::State::Waiting(f), tf).unwrap();
+}
+
+/// Returns the range of addresses `[start, end)` of the code and constants
+/// (`.text` and `.rodata`) of the program image. Until programs are loaded
+/// separately, user code and constants are linked into the kernel image, so
+/// system calls may read from them. The kernel's statics that follow are off
+/// limits.
+#[cfg(not(test))]
+fn image() -> (usize, usize) {
+    extern "C" {
+        static _start: u8;
+        static __rodata_end: u8;
+    }
+
+    unsafe { (&_start as *const u8 as usize, &__rodata_end as *const u8 as usize) }
+}
+
+/// Host tests are not linked into a program image.
+#[cfg(test)]
+fn image() -> (usize, usize) {
+    (0, 0)
+}
+
+/// Checks that process `p` may access the `len` bytes at `addr`: they must lie
+/// within its stack or one of its memory mappings or, for reads only, within
+/// the code and constants of the program image. Fails with `Error::Fault` otherwise.
+fn check_user(p: &process::Process, addr: usize, len: usize, write: bool) -> Result<(), Error> {
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return Err(Error::Fault)
+    };
+
+    let (image_start, image_end) = image();
+    let in_image = !write && addr >= image_start && end <= image_end;
+    if len == 0 || in_image || p.owns(addr, len) {
+        Ok(())
+    } else {
+        Err(Error::Fault)
+    }
+}
+
+/// Copies `dst.len()` bytes from address `src` of process `p` into `dst`.
+/// Fails with `Error::Fault`, copying nothing, if `p` may not read them.
+pub fn copy_from_user(p: &process::Process, src: usize, dst: &mut [u8]) -> Result<(), Error> {
+    check_user(p, src, dst.len(), false)?;
+    unsafe { ptr::copy_nonoverlapping(src as *const u8, dst.as_mut_ptr(), dst.len()) };
+    Ok(())
+}
+
+/// Copies `src` to address `dst` of process `p`. Fails with `Error::Fault`,
+/// copying nothing, if `p` may not write there.
+pub fn copy_to_user(p: &process::Process, dst: usize, src: &[u8]) -> Result<(), Error> {
+    check_user(p, dst, src.len(), true)?;
+    unsafe { ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len()) };
+    Ok(())
+}
+
+/// A pointer to a `T` in the memory of a process, passed to a system call.
+/// It can only be dereferenced through a process that may access it.
+#[derive(Debug)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> UserPtr<T> {
+        UserPtr::new(self.addr)
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T: Copy> UserPtr<T> {
+    /// Returns a pointer to the `T` at `addr`.
+    pub fn new(addr: usize) -> UserPtr<T> {
+        UserPtr { addr, _marker: PhantomData }
+    }
+
+    /// The address this pointer points to.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Checks that the pointer is aligned and that process `p` may access
+    /// the whole `T`.
+    fn check(&self, p: &process::Process, write: bool) -> Result<(), Error> {
+        if self.addr % mem::align_of::<T>() != 0 {
+            return Err(Error::Fault);
+        }
+        check_user(p, self.addr, mem::size_of::<T>(), write)
+    }
+
+    /// Reads the `T` this pointer points to in the memory of process `p`.
+    /// Fails with `Error::Fault` if the pointer is misaligned or `p` may not
+    /// read it.
+    pub fn read(&self, p: &process::Process) -> Result<T, Error> {
+        self.check(p, false)?;
+        Ok(unsafe { ptr::read_volatile(self.addr as *const T) })
+    }
+
+    /// Writes `value` to the `T` this pointer points to in the memory of
+    /// process `p`. Fails with `Error::Fault` if the pointer is misaligned or
+    /// `p` may not write it.
+    pub fn write(&self, p: &process::Process, value: T) -> Result<(), Error> {
+        self.check(p, true)?;
+        unsafe { ptr::write_volatile(self.addr as *mut T, value) };
+        Ok(())
+    }
+}
+
+/// Returns a clone of the current process's descriptor `fd`, if it is open.
+fn current_descriptor(fd: Fd) -> Option<Descriptor> {
+    SCHEDULER.with_current(|p| p.descriptor(fd).cloned()).and_then(|d| d)
+}
 
 /// Sleep for `ms` milliseconds.
 ///
@@ -11,13 +204,14 @@ use process;
 /// In addition to the usual status value, this system call returns one
 /// parameter: the approximate true elapsed time from when `sleep` was called to
 /// when `sleep` returned.
-pub fn sleep(ms: u32, tf: &mut TrapFrame) {
+pub fn sleep(args: &Args, tf: &mut TrapFrame) {
+    let ms = args[0] as u32;
     let start_time = timer::current_time();
     let f = Box::new(move |p: &mut process::Process| {
         let elapsed_time = timer::current_time() - start_time;
         if elapsed_time >= (ms as u64) * 1000 {
             // Return the actual elapsed time from this syscall via x0
-            p.trap_frame.general_registers[31] = elapsed_time / 1000;
+            set_result(&mut p.trap_frame, Ok(elapsed_time / 1000));
             return true;
         } else {
             return false;
@@ -26,24 +220,370 @@ pub fn sleep(ms: u32, tf: &mut TrapFrame) {
     SCHEDULER.switch(process::State::Waiting(f), tf).unwrap();
 }
 
-// TODO: Implement error return
-pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
-    if num == 1 {
-        // Pass x0 as the sleeping time for syscall 1: sleep
-        sleep(tf.general_registers[31] as u32, tf);
+/// Create a pipe.
+///
+/// This system call takes no parameters. It returns two parameters: the file
+/// descriptor of the reading end in `x0` and that of the writing end in `x1`.
+pub fn pipe(_args: &Args, tf: &mut TrapFrame) {
+    let (reader, writer) = process::pipe();
+    let fds = SCHEDULER.with_current(|p| {
+        (p.add_descriptor(Descriptor::PipeReader(reader)),
+         p.add_descriptor(Descriptor::PipeWriter(writer)))
+    });
+
+    match fds {
+        Some((read_fd, write_fd)) => {
+            set_result(tf, Ok(read_fd as u64));
+            tf.set_x(1, write_fd as u64);
+        },
+        None => set_result(tf, Err(Error::Io))
+    }
+}
+
+/// Read up to `len` bytes from `fd` into `buf`.
+///
+/// This system call takes three parameters: the file descriptor, the address
+/// of the buffer and its length. If no data is available, the process blocks
+/// until some is. Returns the number of bytes read, at most `MAX_COPY`, which
+/// is `0` at the end of file (a pipe whose writers are all closed). Fails with
+/// `Error::Fault` if the process may not write to the buffer.
+pub fn read(args: &Args, tf: &mut TrapFrame) {
+    let (fd, buf, len) = (args[0] as Fd, args[1] as usize, args[2] as usize);
+    let descriptor = match current_descriptor(fd) {
+        Some(descriptor) => descriptor,
+        None => return set_result(tf, Err(Error::BadFd))
+    };
+
+    let mut data = vec![0u8; min(len, MAX_COPY)];
+    block_on(move |p| {
+        // Check the buffer before any data is consumed
+        check_user(p, buf, data.len(), true)?;
+        let read = descriptor.read(&mut data).map_err(io_error)?;
+        copy_to_user(p, buf, &data[..read])?;
+        Ok(read as u64)
+    }, tf);
+}
+
+/// Write up to `len` bytes from `buf` to `fd`.
+///
+/// This system call takes three parameters: the file descriptor, the address
+/// of the buffer and its length. If no space is available, the process blocks
+/// until some is. Returns the number of bytes written, which may be less than
+/// `len` and is at most `MAX_COPY`. Fails with `Error::BrokenPipe` if `fd` is
+/// a pipe without readers and with `Error::Fault` if the process may not read
+/// the buffer.
+pub fn write(args: &Args, tf: &mut TrapFrame) {
+    let (fd, buf, len) = (args[0] as Fd, args[1] as usize, args[2] as usize);
+    let descriptor = match current_descriptor(fd) {
+        Some(descriptor) => descriptor,
+        None => return set_result(tf, Err(Error::BadFd))
+    };
+
+    let mut data = vec![0u8; min(len, MAX_COPY)];
+    match SCHEDULER.with_current(|p| copy_from_user(p, buf, &mut data)) {
+        Some(Ok(())) => (),
+        Some(Err(error)) => return set_result(tf, Err(error)),
+        None => return set_result(tf, Err(Error::Io))
+    }
+
+    block_on(move |_| Ok(descriptor.write(&data).map_err(io_error)? as u64), tf);
+}
+
+/// Close `fd`.
+///
+/// This system call takes one parameter: the file descriptor. It returns no
+/// parameters.
+pub fn close(args: &Args, tf: &mut TrapFrame) {
+    let fd = args[0] as Fd;
+    let closed = SCHEDULER.with_current(|p| p.close_descriptor(fd));
+    match closed {
+        Some(Some(_)) => set_result(tf, Ok(0)),
+        _ => set_result(tf, Err(Error::BadFd))
+    }
+}
+
+/// Terminate the calling process.
+///
+/// This system call takes one parameter: the exit code. It does not return.
+pub fn exit(args: &Args, tf: &mut TrapFrame) {
+    let status = process::ExitStatus::Exited(args[0]);
+    SCHEDULER.switch(process::State::Zombie(status), tf).unwrap();
+}
+
+/// Set the real-time parameters of the calling process.
+///
+/// This system call takes two parameters: the period and the budget, both in
+/// microseconds. The process is then guaranteed to run for `budget` in every
+/// `period`, scheduled earliest deadline first ahead of best-effort processes.
+/// A period of `0` makes the process best-effort again. It returns no
+/// parameters. Fails with `Error::Invalid` if the budget is `0` or exceeds
+/// the period, with `Error::Busy` if the real-time processes would need more
+/// than 100% of the CPU, and with `Error::NoProcess` if no process is running.
+pub fn sched_realtime(args: &Args, tf: &mut TrapFrame) {
+    let result = match SCHEDULER.set_realtime(args[0], args[1]) {
+        Ok(()) => Ok(0),
+        Err(RealTimeError::NoProcess) => Err(Error::NoProcess),
+        Err(RealTimeError::Invalid) => Err(Error::Invalid),
+        Err(RealTimeError::Rejected) => Err(Error::Busy)
+    };
+    set_result(tf, result);
+}
+
+/// Map anonymous memory into the calling process.
+///
+/// This system call takes one parameter: the length of the mapping in bytes,
+/// which is rounded up to whole pages. It returns one parameter: the start
+/// address of the new, zeroed mapping. The memory stays valid until it is
+/// unmapped or the process terminates. Fails with `Error::Invalid` if the
+/// length is `0` or too large, and with `Error::NoMem` if there is not enough
+/// memory.
+pub fn mmap(args: &Args, tf: &mut TrapFrame) {
+    let len = args[0] as usize;
+    let result = if len == 0 || len > process::Mapping::MAX_SIZE {
+        Err(Error::Invalid)
     } else {
-        unimplemented!();
+        match SCHEDULER.with_current(|p| p.map(len)) {
+            Some(Some(addr)) => Ok(addr as u64),
+            _ => Err(Error::NoMem)
+        }
+    };
+    set_result(tf, result);
+}
+
+/// Unmap memory mapped with `mmap`.
+///
+/// This system call takes one parameter: the start address of the mapping,
+/// as returned by `mmap`. The whole mapping is freed. It returns no
+/// parameters. Fails with `Error::Invalid` if no mapping starts at the
+/// address.
+pub fn munmap(args: &Args, tf: &mut TrapFrame) {
+    let addr = args[0] as usize;
+    match SCHEDULER.with_current(|p| p.unmap(addr)) {
+        Some(true) => set_result(tf, Ok(0)),
+        _ => set_result(tf, Err(Error::Invalid))
+    }
+}
+
+/// A system call handler. The handler stores the result into the trap frame
+/// with `set_result`, right away or, for a blocking call, once it completes.
+pub type Handler = fn(&Args, &mut TrapFrame);
+
+/// Give up the rest of the time slice.
+///
+/// This system call takes no parameters and returns no parameters. The calling
+/// process stays ready and runs again when it is next picked by the scheduler.
+pub fn yield_now(_args: &Args, tf: &mut TrapFrame) {
+    set_result(tf, Ok(0));
+    SCHEDULER.switch(process::State::Ready, tf).unwrap();
+}
+
+/// Get the ID of the calling process.
+///
+/// This system call takes no parameters. It returns one parameter: the
+/// process ID, as known to the scheduler. `TPIDR_EL0`, saved in the trap
+/// frame, is writable from user space and cannot be trusted.
+pub fn getpid(_args: &Args, tf: &mut TrapFrame) {
+    match SCHEDULER.current() {
+        Some(pid) => set_result(tf, Ok(pid)),
+        None => set_result(tf, Err(Error::Io))
+    }
+}
+
+/// Get the ID of the parent of the calling process.
+///
+/// This system call takes no parameters. It returns one parameter: the ID of
+/// the process that created the calling one, or `0` if it has no parent.
+pub fn getppid(_args: &Args, tf: &mut TrapFrame) {
+    let ppid = SCHEDULER.with_current(|p| p.parent).and_then(|p| p);
+    set_result(tf, Ok(ppid.unwrap_or(0)));
+}
+
+/// Get the time since boot.
+///
+/// This system call takes no parameters. It returns one parameter: the number
+/// of microseconds since the system timer started.
+pub fn uptime_us(_args: &Args, tf: &mut TrapFrame) {
+    set_result(tf, Ok(timer::current_time()));
+}
+
+/// Splits `us` microseconds into whole seconds and the remaining nanoseconds.
+pub fn timespec(us: u64) -> (u64, u64) {
+    (us / 1_000_000, (us % 1_000_000) * 1000)
+}
+
+/// Read a clock.
+///
+/// This system call takes one parameter: the ID of a `Clock`. It returns two
+/// parameters: the time in seconds in `x0` and the nanoseconds within that
+/// second in `x1`. Fails with `Error::Invalid` for an unknown clock.
+pub fn clock_gettime(args: &Args, tf: &mut TrapFrame) {
+    match Clock::from_id(args[0]) {
+        Some(Clock::Monotonic) => {
+            let (secs, nanos) = timespec(timer::current_time());
+            set_result(tf, Ok(secs));
+            tf.set_x(1, nanos);
+        },
+        None => set_result(tf, Err(Error::Invalid))
     }
 }
 
-pub fn call_sleep(ms: u32) -> u32 {
-    let ret: u32;
-    unsafe {
-        asm!("mov x0, $1
-              svc 1
-              mov $0, x0"
-              : "=r"(ret) : "r"(ms) : "x0" : "volatile"
-        );
+/// Wait on a futex.
+///
+/// This system call takes three parameters: the address of a 32-bit futex
+/// word, the value it is expected to hold and a timeout in microseconds, `0`
+/// for none. If the word holds the expected value, the process blocks until
+/// it is woken by `futex_wake` on the same address or the timeout expires.
+/// The check and the start of the wait happen atomically with respect to
+/// `futex_wake`. It returns no parameters. Fails with `Error::Again` if the
+/// word does not hold the expected value, with `Error::TimedOut` if the
+/// timeout expired, with `Error::Invalid` if the address is not aligned and
+/// with `Error::Fault` if the process may not read the word.
+pub fn futex_wait(args: &Args, tf: &mut TrapFrame) {
+    let (addr, expected, timeout) = (args[0] as usize, args[1] as u32, args[2]);
+    if addr % 4 != 0 {
+        return set_result(tf, Err(Error::Invalid));
+    }
+
+    match SCHEDULER.with_current(|p| UserPtr::<u32>::new(addr).read(p)) {
+        Some(Ok(value)) if value == expected => (),
+        Some(Ok(_)) => return set_result(tf, Err(Error::Again)),
+        Some(Err(error)) => return set_result(tf, Err(error)),
+        None => return set_result(tf, Err(Error::Io))
+    }
+
+    let waiter = FUTEXES.with(|futexes| futexes.wait(addr));
+    let start = timer::current_time();
+    let f = Box::new(move |p: &mut process::Process| {
+        if waiter.woken() {
+            set_result(&mut p.trap_frame, Ok(0));
+            true
+        } else if timeout != 0 && timer::current_time() - start >= timeout {
+            set_result(&mut p.trap_frame, Err(Error::TimedOut));
+            true
+        } else {
+            false
+        }
+    });
+    SCHEDULER.switch(process::State::Waiting(f), tf).unwrap();
+}
+
+/// Wake processes waiting on a futex.
+///
+/// This system call takes two parameters: the address of the futex word and
+/// the maximum number of processes to wake. Waiters are woken in the order
+/// they started waiting. It returns one parameter: the number of processes
+/// woken.
+pub fn futex_wake(args: &Args, tf: &mut TrapFrame) {
+    let (addr, n) = (args[0] as usize, args[1] as usize);
+    let woken = FUTEXES.with(|futexes| futexes.wake(addr, n));
+    set_result(tf, Ok(woken as u64));
+}
+
+/// Create an alarm.
+///
+/// This system call takes no parameters. It returns one parameter: the file
+/// descriptor of a new, disarmed alarm. Reading from the descriptor blocks
+/// until the alarm expires and returns the number of expirations since the
+/// last read as a little-endian `u64`; the buffer must hold 8 bytes.
+pub fn timer_create(_args: &Args, tf: &mut TrapFrame) {
+    let alarm = process::Alarm::new();
+    match SCHEDULER.with_current(|p| p.add_descriptor(Descriptor::Alarm(alarm))) {
+        Some(fd) => set_result(tf, Ok(fd as u64)),
+        None => set_result(tf, Err(Error::Io))
+    }
+}
+
+/// Arm or disarm an alarm.
+///
+/// This system call takes three parameters: the file descriptor of the alarm,
+/// the time in microseconds until it first expires and the interval in
+/// microseconds at which it expires after that. An initial time of `0`
+/// disarms the alarm; an interval of `0` makes it one-shot. Expirations that
+/// have not been read are discarded. It returns no parameters. Fails with
+/// `Error::BadFd` if the descriptor is not an alarm.
+pub fn timer_set(args: &Args, tf: &mut TrapFrame) {
+    let (fd, initial, interval) = (args[0] as Fd, args[1], args[2]);
+    match current_descriptor(fd) {
+        Some(Descriptor::Alarm(alarm)) => {
+            alarm.set(timer::current_time(), initial, interval);
+            set_result(tf, Ok(0));
+        },
+        _ => set_result(tf, Err(Error::BadFd))
+    }
+}
+
+/// How the arguments of a system call are shown when it is traced.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// An integer, e.g. a length or a file descriptor, shown in decimal.
+    Int,
+    /// An address, shown in hexadecimal.
+    Ptr,
+}
+
+/// An entry of the system call table.
+pub struct Syscall {
+    /// The name of the system call.
+    pub name: &'static str,
+    /// The arguments the system call takes.
+    pub args: &'static [Arg],
+    /// The function implementing the system call.
+    pub handler: Handler,
+}
+
+/// The system call table, indexed by system call number.
+pub static SYSCALLS: [Option<Syscall>; 19] = [
+    None,
+    Some(Syscall { name: "sleep", args: &[Arg::Int], handler: sleep }),
+    Some(Syscall { name: "pipe", args: &[], handler: pipe }),
+    Some(Syscall { name: "read", args: &[Arg::Int, Arg::Ptr, Arg::Int], handler: read }),
+    Some(Syscall { name: "write", args: &[Arg::Int, Arg::Ptr, Arg::Int], handler: write }),
+    Some(Syscall { name: "close", args: &[Arg::Int], handler: close }),
+    Some(Syscall { name: "exit", args: &[Arg::Int], handler: exit }),
+    Some(Syscall { name: "sched_realtime", args: &[Arg::Int, Arg::Int], handler: sched_realtime }),
+    Some(Syscall { name: "mmap", args: &[Arg::Int], handler: mmap }),
+    Some(Syscall { name: "munmap", args: &[Arg::Ptr], handler: munmap }),
+    Some(Syscall { name: "yield", args: &[], handler: yield_now }),
+    Some(Syscall { name: "getpid", args: &[], handler: getpid }),
+    Some(Syscall { name: "getppid", args: &[], handler: getppid }),
+    Some(Syscall { name: "uptime_us", args: &[], handler: uptime_us }),
+    Some(Syscall { name: "clock_gettime", args: &[Arg::Int], handler: clock_gettime }),
+    Some(Syscall { name: "futex_wait", args: &[Arg::Ptr, Arg::Int, Arg::Int], handler: futex_wait }),
+    Some(Syscall { name: "futex_wake", args: &[Arg::Ptr, Arg::Int], handler: futex_wake }),
+    Some(Syscall { name: "timer_create", args: &[], handler: timer_create }),
+    Some(Syscall { name: "timer_set", args: &[Arg::Int, Arg::Int, Arg::Int], handler: timer_set }),
+];
+
+/// Returns the entry of system call `num`, if there is one.
+pub fn lookup(num: u16) -> Option<&'static Syscall> {
+    SYSCALLS.get(num as usize).and_then(|s| s.as_ref())
+}
+
+/// Handles system call `num` made by the current process, whose state is in
+/// `tf`.
+///
+/// System calls take up to six arguments in `x0`-`x5`. They return a value in
+/// `x0` (and possibly more in `x1`-`x6`) plus a status in `x7`, which is `0`
+/// on success or an `Error` code. Unknown system calls fail with
+/// `Error::NoSys`.
+///
+/// System calls of processes with their `traced` flag set are logged to the
+/// console.
+pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
+    // The thread ID in `tf` is writable from EL0, so ask the scheduler
+    let traced = match SCHEDULER.current() {
+        Some(pid) if SCHEDULER.with_process(pid, |p| p.traced) == Some(true) => Some(pid),
+        _ => None
+    };
+
+    match (lookup(num), traced) {
+        (Some(syscall), Some(pid)) => strace::trace(pid, num, syscall, tf),
+        (Some(syscall), None) => (syscall.handler)(&args(tf), tf),
+        (None, traced) => {
+            if let Some(pid) = traced {
+                kprintln!("[{}] syscall_{}() = {:?}", pid, num, Error::NoSys);
+            }
+            set_result(tf, Err(Error::NoSys))
+        }
     }
-    return ret;
-}
\ No newline at end of file
+}
@@ -0,0 +1,561 @@
+mod syscall {
+    use process::{Process, Stack};
+    use traps::TrapFrame;
+    use traps::syscall::{self, Clock, Error, UserPtr};
+
+    /// Returns a trap frame with `x0`-`x5` set to `1`-`6`.
+    fn frame_with_args() -> TrapFrame {
+        let mut tf = TrapFrame::default();
+        for n in 0..6 {
+            tf.set_x(n, n as u64 + 1);
+        }
+        tf
+    }
+
+    #[test]
+    fn args_from_registers() {
+        assert_eq!(syscall::args(&frame_with_args()), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn result_round_trip() {
+        let mut tf = TrapFrame::default();
+        syscall::set_result(&mut tf, Ok(42));
+        assert_eq!(tf.x(0), 42);
+        assert_eq!(tf.x(7), 0);
+        assert_eq!(syscall::result(&tf), Ok(42));
+
+        syscall::set_result(&mut tf, Err(Error::BadFd));
+        assert_eq!(tf.x(7), Error::BadFd as u64);
+        assert_eq!(syscall::result(&tf), Err(Error::BadFd));
+    }
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(Error::from_status(0), Ok(()));
+        for &e in [Error::NoProcess, Error::Io, Error::BadFd, Error::Again, Error::NoMem, Error::Fault,
+                   Error::Busy, Error::Invalid, Error::BrokenPipe, Error::NoSys,
+                   Error::TimedOut].iter() {
+            assert_eq!(Error::from_status(e as u64), Err(e));
+        }
+        assert_eq!(Error::from_status(1000), Err(Error::Unknown));
+    }
+
+    #[test]
+    fn table_numbers() {
+        let names = ["sleep", "pipe", "read", "write", "close", "exit", "sched_realtime",
+                     "mmap", "munmap", "yield", "getpid", "getppid", "uptime_us",
+                     "clock_gettime", "futex_wait", "futex_wake",
+                     "timer_create", "timer_set"];
+        assert!(syscall::lookup(0).is_none());
+        for (i, name) in names.iter().enumerate() {
+            let entry = syscall::lookup(i as u16 + 1).expect("syscall");
+            assert_eq!(entry.name, *name);
+        }
+        assert!(syscall::lookup(names.len() as u16 + 1).is_none());
+        assert!(syscall::lookup(!0).is_none());
+    }
+
+    #[test]
+    fn getpid_returns_thread_id() {
+        let mut tf = frame_with_args();
+        tf.thread_id = 7;
+        syscall::getpid(&syscall::args(&tf), &mut tf);
+        assert_eq!(syscall::result(&tf), Ok(7));
+    }
+
+    #[test]
+    fn clock_ids() {
+        assert_eq!(Clock::from_id(1), Some(Clock::Monotonic));
+        assert_eq!(Clock::from_id(0), None);
+        assert_eq!(Clock::from_id(!0), None);
+    }
+
+    #[test]
+    fn timespec_split() {
+        assert_eq!(syscall::timespec(0), (0, 0));
+        assert_eq!(syscall::timespec(999_999), (0, 999_999_000));
+        assert_eq!(syscall::timespec(1_000_000), (1, 0));
+        assert_eq!(syscall::timespec(3_000_042), (3, 42_000));
+    }
+
+    #[test]
+    fn copy_within_stack() {
+        let p = Process::new().expect("process");
+        let bottom = p.stack.bottom().as_u64() as usize;
+
+        syscall::copy_to_user(&p, bottom + 16, b"hello").expect("copy_to_user");
+        let mut buf = [0u8; 5];
+        syscall::copy_from_user(&p, bottom + 16, &mut buf).expect("copy_from_user");
+        assert_eq!(&buf, b"hello");
+
+        let top = bottom + Stack::SIZE;
+        assert_eq!(syscall::copy_to_user(&p, top - 5, b"hello"), Ok(()));
+        assert_eq!(syscall::copy_to_user(&p, top - 4, b"hello"), Err(Error::Fault));
+        assert_eq!(syscall::copy_from_user(&p, bottom - 1, &mut buf), Err(Error::Fault));
+    }
+
+    #[test]
+    fn copy_within_mapping() {
+        let mut p = Process::new().expect("process");
+        let addr = p.map(100).expect("map");
+
+        assert_eq!(syscall::copy_to_user(&p, addr, &[7; 100]), Ok(()));
+        p.unmap(addr);
+        assert_eq!(syscall::copy_to_user(&p, addr, &[7; 100]), Err(Error::Fault));
+    }
+
+    #[test]
+    fn copy_rejects_bad_ranges() {
+        let p = Process::new().expect("process");
+        let mut buf = [0u8; 8];
+        assert_eq!(syscall::copy_from_user(&p, 0, &mut buf), Err(Error::Fault));
+        assert_eq!(syscall::copy_from_user(&p, !0 - 3, &mut buf), Err(Error::Fault));
+        assert_eq!(syscall::copy_from_user(&p, 0, &mut []), Ok(()));
+    }
+
+    #[test]
+    fn user_ptr() {
+        let p = Process::new().expect("process");
+        let bottom = p.stack.bottom().as_u64() as usize;
+
+        let ptr = UserPtr::<u32>::new(bottom + 64);
+        ptr.write(&p, 0xdead_beef).expect("write");
+        assert_eq!(ptr.read(&p), Ok(0xdead_beef));
+
+        assert_eq!(UserPtr::<u32>::new(bottom + 65).read(&p), Err(Error::Fault));
+        assert_eq!(UserPtr::<u32>::new(0).read(&p), Err(Error::Fault));
+    }
+}
+
+mod trap_frame {
+    use std::mem;
+    use std::ptr;
+
+    use traps::TrapFrame;
+
+    /// A descending stack that mimics the `stp` pushes of `init.S`.
+    struct Stack {
+        memory: [u128; TrapFrame::SIZE / 16],
+        sp: usize,
+    }
+
+    impl Stack {
+        fn new() -> Stack {
+            Stack { memory: [0; TrapFrame::SIZE / 16], sp: TrapFrame::SIZE }
+        }
+
+        fn bytes(&mut self) -> *mut u8 {
+            self.memory.as_mut_ptr() as *mut u8
+        }
+
+        /// `stp a, b, [SP, #-16]!`
+        fn push_x(&mut self, a: u64, b: u64) {
+            self.sp -= 16;
+            unsafe {
+                ptr::write(self.bytes().add(self.sp) as *mut u64, a);
+                ptr::write(self.bytes().add(self.sp + 8) as *mut u64, b);
+            }
+        }
+
+        /// `stp qa, qb, [SP, #-32]!`
+        fn push_q(&mut self, a: u128, b: u128) {
+            self.sp -= 32;
+            unsafe {
+                ptr::write(self.bytes().add(self.sp) as *mut u128, a);
+                ptr::write(self.bytes().add(self.sp + 16) as *mut u128, b);
+            }
+        }
+
+        fn frame(&mut self) -> TrapFrame {
+            assert_eq!(self.sp, 0, "frame size mismatch");
+            unsafe { ptr::read(self.bytes() as *const TrapFrame) }
+        }
+    }
+
+    const HANDLER_LR: u64 = 0xffff_0000;
+
+    fn q_value(n: u64) -> u128 {
+        ((n as u128) << 64) | (1000 + n as u128)
+    }
+
+    /// Pushes a frame exactly as `HANDLER` and `context_save` do, with `xn`
+    /// holding `n` and `qn` holding `q_value(n)`.
+    fn pushed_frame() -> TrapFrame {
+        let mut stack = Stack::new();
+
+        // HANDLER
+        stack.push_x(30, 0);
+
+        // context_save: 64-bit registers, `x30` now holding HANDLER's `lr`
+        for pair in 0..14 {
+            let n = 2 * pair + 1;
+            stack.push_x(n, n + 1);
+        }
+        stack.push_x(29, HANDLER_LR);
+
+        // 128-bit registers
+        stack.push_q(q_value(0), q_value(31));
+        for pair in 0..15 {
+            let n = 2 * pair + 1;
+            stack.push_q(q_value(n), q_value(n + 1));
+        }
+
+        // Special registers
+        stack.push_x(0x3c5, 0x8_0000);
+        stack.push_x(0x40_0000, 7);
+        stack.frame()
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(mem::size_of::<TrapFrame>(), 800);
+        assert_eq!(mem::size_of::<TrapFrame>(), TrapFrame::SIZE);
+    }
+
+    #[test]
+    fn field_offsets() {
+        let tf = TrapFrame::default();
+        let base = &tf as *const TrapFrame as usize;
+        let offset = |field: usize| field - base;
+
+        assert_eq!(offset(&tf.stack_pointer as *const _ as usize), 0);
+        assert_eq!(offset(&tf.thread_id as *const _ as usize), 8);
+        assert_eq!(offset(&tf.program_state as *const _ as usize), 16);
+        assert_eq!(offset(&tf.program_counter as *const _ as usize), 24);
+        assert_eq!(offset(&tf.floating_point_registers as *const _ as usize), 32);
+        assert_eq!(offset(&tf.general_registers as *const _ as usize), 32 + 32 * 16);
+    }
+
+    #[test]
+    fn special_registers() {
+        let tf = pushed_frame();
+        assert_eq!(tf.stack_pointer, 0x40_0000);
+        assert_eq!(tf.thread_id, 7);
+        assert_eq!(tf.spsr(), 0x3c5);
+        assert_eq!(tf.elr(), 0x8_0000);
+        assert_eq!(tf.handler_lr(), HANDLER_LR);
+    }
+
+    #[test]
+    fn general_registers() {
+        let tf = pushed_frame();
+        for n in 0..31 {
+            assert_eq!(tf.x(n), n as u64, "x{}", n);
+        }
+        assert_eq!(tf.lr(), 30);
+    }
+
+    #[test]
+    fn floating_point_registers() {
+        let tf = pushed_frame();
+        for n in 0..32 {
+            assert_eq!(tf.q(n), q_value(n as u64), "q{}", n);
+        }
+    }
+
+    #[test]
+    fn setters_round_trip() {
+        let mut tf = TrapFrame::default();
+        for n in 0..31 {
+            tf.set_x(n, 100 + n as u64);
+        }
+        for n in 0..32 {
+            tf.set_q(n, 200 + n as u128);
+        }
+        tf.set_handler_lr(HANDLER_LR);
+
+        for n in 0..31 {
+            assert_eq!(tf.x(n), 100 + n as u64);
+        }
+        for n in 0..32 {
+            assert_eq!(tf.q(n), 200 + n as u128);
+        }
+        assert_eq!(tf.handler_lr(), HANDLER_LR);
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_x31() {
+        TrapFrame::default().x(31);
+    }
+}
+
+mod report {
+    use traps::report::Spsr;
+
+    #[test]
+    fn spsr_modes() {
+        assert_eq!(Spsr(0b0000).mode(), "EL0t");
+        assert_eq!(Spsr(0b0100).mode(), "EL1t");
+        assert_eq!(Spsr(0b0101).mode(), "EL1h");
+        assert_eq!(Spsr(0b1_0000).mode(), "AArch32");
+        assert_eq!(Spsr(0b0011).mode(), "invalid");
+    }
+
+    #[test]
+    fn spsr_display() {
+        assert_eq!(format!("{}", Spsr(0x3c5)), "0x000003c5 [---- DAIF EL1h]");
+        assert_eq!(format!("{}", Spsr(0x6000_0000)), "0x60000000 [-ZC- ---- EL0t]");
+        assert_eq!(format!("{}", Spsr((1 << 31) | (1 << 21) | (1 << 7))),
+                   "0x80200080 [N--- --I- SS EL0t]");
+    }
+}
+
+mod irq {
+    use pi::interrupt::Interrupt;
+
+    use traps::TrapFrame;
+    use traps::irq::{Irqs, IrqStat};
+
+    fn nop(_: Interrupt, _: &mut TrapFrame) {}
+
+    fn bit(int: Interrupt) -> u64 {
+        1 << int as u64
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let mut irqs = Irqs::new();
+        assert!(irqs.handler(Interrupt::Uart).is_none());
+
+        assert!(irqs.register(Interrupt::Uart, nop));
+        assert!(!irqs.register(Interrupt::Uart, nop));
+        assert!(irqs.handler(Interrupt::Uart).is_some());
+        assert!(irqs.handler(Interrupt::Timer1).is_none());
+
+        assert!(irqs.unregister(Interrupt::Uart).is_some());
+        assert!(irqs.unregister(Interrupt::Uart).is_none());
+        assert!(irqs.register(Interrupt::Uart, nop));
+    }
+
+    #[test]
+    fn dispatch_returns_handlers() {
+        let mut irqs = Irqs::new();
+        irqs.register(Interrupt::Timer1, nop);
+        irqs.register(Interrupt::Gpio3, nop);
+
+        let handlers = irqs.dispatch(bit(Interrupt::Timer1) | bit(Interrupt::Gpio3), 0);
+        assert!(handlers[Interrupt::Timer1 as usize].is_some());
+        assert!(handlers[Interrupt::Gpio3 as usize].is_some());
+        assert!(handlers[Interrupt::Uart as usize].is_none());
+        assert_eq!(irqs.spurious(), 0);
+        assert_eq!(irqs.unhandled(), 0);
+    }
+
+    #[test]
+    fn counts_spurious() {
+        let mut irqs = Irqs::new();
+        irqs.dispatch(0, 0);
+        // Interrupts without an `Interrupt` and the bank summary bits do not
+        // count as pending
+        irqs.dispatch(1 << 63, 0b11 << 8);
+        assert_eq!(irqs.spurious(), 2);
+        assert_eq!(irqs.unhandled(), 0);
+    }
+
+    #[test]
+    fn counts_unhandled() {
+        let mut irqs = Irqs::new();
+        irqs.register(Interrupt::Timer1, nop);
+
+        irqs.dispatch(bit(Interrupt::Timer1) | bit(Interrupt::Usb) | bit(Interrupt::Uart), 0);
+        assert_eq!(irqs.unhandled(), 2);
+        irqs.dispatch(0, 0b101);
+        assert_eq!(irqs.unhandled(), 4);
+        assert_eq!(irqs.spurious(), 0);
+    }
+
+    #[test]
+    fn dispatch_basic() {
+        let mut irqs = Irqs::new();
+        assert!(irqs.register(Interrupt::ArmTimer, nop));
+
+        // ARM timer and doorbell 0, plus a GPU summary bit
+        let handlers = irqs.dispatch(0, 0b101 | 1 << 8);
+        assert!(handlers[Interrupt::ArmTimer as usize].is_some());
+        assert!(handlers[Interrupt::Doorbell0 as usize].is_none());
+        assert_eq!(irqs.stat(Interrupt::ArmTimer).count, 1);
+        assert_eq!(irqs.stat(Interrupt::Doorbell0).count, 1);
+        assert_eq!(irqs.unhandled(), 1);
+        assert_eq!(irqs.spurious(), 0);
+    }
+
+    #[test]
+    fn stats() {
+        let mut irqs = Irqs::new();
+        irqs.register(Interrupt::Timer1, nop);
+        assert_eq!(irqs.stat(Interrupt::Timer1), IrqStat::default());
+
+        irqs.dispatch(bit(Interrupt::Timer1) | bit(Interrupt::Uart), 0);
+        irqs.record(Interrupt::Timer1, 300);
+        irqs.dispatch(bit(Interrupt::Timer1), 0);
+        irqs.record(Interrupt::Timer1, 100);
+
+        let stat = irqs.stat(Interrupt::Timer1);
+        assert_eq!(stat, IrqStat { count: 2, total_cycles: 400, max_cycles: 300 });
+        assert_eq!(stat.average_cycles(), 200);
+        assert_eq!(irqs.stat(Interrupt::Uart).count, 1);
+        assert_eq!(irqs.stat(Interrupt::Usb).average_cycles(), 0);
+    }
+}
+
+mod syndrome {
+    use traps::{Syndrome, Fault, DataAccess};
+
+    /// Returns the kind and level of the abort described by `esr`.
+    fn fault(esr: u32) -> (Fault, Option<u8>) {
+        match Syndrome::from(esr) {
+            Syndrome::DataAbort { kind, level, .. } => (kind, level),
+            Syndrome::InstructionAbort { kind, level, .. } => (kind, level),
+            other => panic!("not an abort: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn data_abort_without_valid_syndrome() {
+        // A write to an unmapped level 1 address from EL1
+        assert_eq!(Syndrome::from(0x9600_0045), Syndrome::DataAbort {
+            kind: Fault::Translation,
+            level: Some(1),
+            write: true,
+            access: None,
+            far_valid: true,
+            external: false,
+            cache_maintenance: false,
+            s1ptw: false,
+        });
+    }
+
+    #[test]
+    fn data_abort_with_valid_syndrome() {
+        // `str x3, [..]` from EL0: ISV, 8 bytes, SRT 3, SF, WnR, level 3
+        assert_eq!(Syndrome::from(0x93C3_8047), Syndrome::DataAbort {
+            kind: Fault::Translation,
+            level: Some(3),
+            write: true,
+            access: Some(DataAccess {
+                size: 8,
+                sign_extend: false,
+                register: 3,
+                sixty_four: true,
+                acquire_release: false,
+            }),
+            far_valid: true,
+            external: false,
+            cache_maintenance: false,
+            s1ptw: false,
+        });
+
+        // A 2-byte, sign extended load-acquire into w1
+        match Syndrome::from(0x9361_4004) {
+            Syndrome::DataAbort { write, access: Some(access), .. } => {
+                assert!(!write);
+                assert_eq!(access, DataAccess {
+                    size: 2,
+                    sign_extend: true,
+                    register: 1,
+                    sixty_four: false,
+                    acquire_release: true,
+                });
+            },
+            other => panic!("unexpected syndrome: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn data_abort_flags() {
+        match Syndrome::from(0x9600_0790) {
+            Syndrome::DataAbort { far_valid, external, cache_maintenance, s1ptw, write, .. } => {
+                assert!(!far_valid && external && cache_maintenance && s1ptw && !write);
+            },
+            other => panic!("unexpected syndrome: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn instruction_abort() {
+        assert_eq!(Syndrome::from(0x8200_000F), Syndrome::InstructionAbort {
+            kind: Fault::Permission,
+            level: Some(3),
+            far_valid: true,
+            external: false,
+            s1ptw: false,
+        });
+
+        match Syndrome::from(0x8600_0410) {
+            Syndrome::InstructionAbort { kind, far_valid, .. } => {
+                assert_eq!(kind, Fault::SyncExternal);
+                assert!(!far_valid);
+            },
+            other => panic!("unexpected syndrome: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn fault_status_codes() {
+        assert_eq!(fault(0x9600_0002), (Fault::AddressSize, Some(2)));
+        assert_eq!(fault(0x9600_0004), (Fault::Translation, Some(0)));
+        assert_eq!(fault(0x9600_000B), (Fault::AccessFlag, Some(3)));
+        assert_eq!(fault(0x9600_000D), (Fault::Permission, Some(1)));
+        assert_eq!(fault(0x9600_0010), (Fault::SyncExternal, None));
+        assert_eq!(fault(0x9600_0015), (Fault::SyncExternalOnWalk, Some(1)));
+        assert_eq!(fault(0x9600_0018), (Fault::SyncParity, None));
+        assert_eq!(fault(0x9600_001F), (Fault::SyncParityOnWalk, Some(3)));
+        assert_eq!(fault(0x9600_0021), (Fault::Alignment, None));
+        assert_eq!(fault(0x9600_0030), (Fault::TlbConflict, None));
+
+        // Reserved and implementation defined codes are kept
+        assert_eq!(fault(0x9600_0008), (Fault::Other(0b001000), None));
+        assert_eq!(fault(0x9600_0034), (Fault::Other(0b110100), None));
+    }
+
+    #[test]
+    fn faults_without_level() {
+        // A write hitting a synchronous external abort from EL1
+        assert_eq!(Syndrome::from(0x9600_0250), Syndrome::DataAbort {
+            kind: Fault::SyncExternal,
+            level: None,
+            write: true,
+            access: None,
+            far_valid: true,
+            external: true,
+            cache_maintenance: false,
+            s1ptw: false,
+        });
+
+        // `ldr w2, [..]` from EL0 at a misaligned address: ISV, 4 bytes, SRT 2
+        assert_eq!(Syndrome::from(0x9382_0021), Syndrome::DataAbort {
+            kind: Fault::Alignment,
+            level: None,
+            write: false,
+            access: Some(DataAccess {
+                size: 4,
+                sign_extend: false,
+                register: 2,
+                sixty_four: false,
+                acquire_release: false,
+            }),
+            far_valid: true,
+            external: false,
+            cache_maintenance: false,
+            s1ptw: false,
+        });
+
+        // An instruction fetch from EL0 hitting a TLB conflict
+        assert_eq!(Syndrome::from(0x8200_0030), Syndrome::InstructionAbort {
+            kind: Fault::TlbConflict,
+            level: None,
+            far_valid: true,
+            external: false,
+            s1ptw: false,
+        });
+    }
+
+    #[test]
+    fn other_syndromes_unaffected() {
+        assert_eq!(Syndrome::from(0x5600_0001), Syndrome::Svc(1));
+        assert_eq!(Syndrome::from(0xF200_00E9), Syndrome::Brk(233));
+        assert_eq!(Syndrome::from(0x1E00_0000), Syndrome::SimdFp);
+    }
+}
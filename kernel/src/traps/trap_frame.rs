@@ -1,3 +1,25 @@
+use std::mem;
+
+/// The state of a process saved on exception entry, as laid out in memory by
+/// `HANDLER` and `context_save` in `init.S` and read back by
+/// `context_restore`.
+///
+/// The registers are pushed in pairs onto a descending stack, so the pair
+/// pushed last comes first:
+///
+///   * `general_registers` holds `x29` and the link register `HANDLER`
+///     returns to (`[0]`, `[1]`), then the pairs `x27`/`x28` down to
+///     `x1`/`x2` (`[2]`-`[29]`), then the `x30` and `x0` of the interrupted
+///     code, pushed by `HANDLER` itself (`[30]`, `[31]`).
+///
+///   * `floating_point_registers` holds the pairs `q29`/`q30` down to
+///     `q1`/`q2` (`[0]`-`[29]`), then `q0` and `q31` (`[30]`, `[31]`).
+///
+/// The `q` registers of a user process are only saved and restored while
+/// it may use them (see `Scheduler::switch()`); otherwise their space in the
+/// frame is left as is.
+///
+/// Use the accessors rather than indexing the arrays directly.
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone)]
 pub struct TrapFrame {
@@ -5,6 +27,119 @@ pub struct TrapFrame {
     pub thread_id: u64,        // TPIDR_ELs
     pub program_state: u64,   // SPSR_ELx
     pub program_counter: u64, // ELR_ELx
-    pub floating_point_registers: [u128; 32], // Order: TODO
-    pub general_registers: [u64; 32] // Order: TODO (general_resgiters[30] is "reserved" field)
+    pub floating_point_registers: [u128; 32], // See `q()`
+    pub general_registers: [u64; 32] // See `x()`
+}
+
+/// Fails to compile unless a `TrapFrame` is exactly as large as the frame
+/// `init.S` pushes (`add SP, SP, #800` in `context_save`).
+const _SIZE_MATCHES_INIT_S: [(); 0] = [(); TrapFrame::SIZE - mem::size_of::<TrapFrame>()];
+const _SIZE_FITS_INIT_S: [(); 0] = [(); mem::size_of::<TrapFrame>() - TrapFrame::SIZE];
+
+/// Fails to compile if the four special registers would not end on a
+/// boundary `q` registers can be stored at, i.e. if padding would be inserted
+/// before `floating_point_registers`.
+const _NO_PADDING: [(); 0] = [(); (4 * mem::size_of::<u64>()) % mem::align_of::<u128>()];
+
+impl TrapFrame {
+    /// The size of a trap frame in bytes.
+    pub const SIZE: usize = 800;
+
+    /// The index of `xn` in `general_registers`.
+    fn x_index(n: usize) -> usize {
+        match n {
+            0 => 31,
+            1..30 => 2 * (14 - (n - 1) / 2) + (n - 1) % 2,
+            30 => 30,
+            _ => panic!("no register x{}", n)
+        }
+    }
+
+    /// The index of `qn` in `floating_point_registers`.
+    fn q_index(n: usize) -> usize {
+        match n {
+            0 => 30,
+            1..31 => 2 * (15 - (n + 1) / 2) + (n + 1) % 2,
+            31 => 31,
+            _ => panic!("no register q{}", n)
+        }
+    }
+
+    /// Returns general purpose register `xn` of the interrupted code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than `30`.
+    pub fn x(&self, n: usize) -> u64 {
+        self.general_registers[Self::x_index(n)]
+    }
+
+    /// Sets general purpose register `xn` of the interrupted code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than `30`.
+    pub fn set_x(&mut self, n: usize, value: u64) {
+        self.general_registers[Self::x_index(n)] = value;
+    }
+
+    /// Returns the link register (`x30`) of the interrupted code.
+    pub fn lr(&self) -> u64 {
+        self.x(30)
+    }
+
+    /// Sets the link register (`x30`) of the interrupted code.
+    pub fn set_lr(&mut self, value: u64) {
+        self.set_x(30, value);
+    }
+
+    /// Returns the link register `context_save` returns to in `HANDLER`. It
+    /// belongs to the exception, not to the interrupted code, and must be
+    /// preserved across context switches.
+    pub fn handler_lr(&self) -> u64 {
+        self.general_registers[1]
+    }
+
+    /// Sets the link register `context_save` returns to in `HANDLER`.
+    pub fn set_handler_lr(&mut self, value: u64) {
+        self.general_registers[1] = value;
+    }
+
+    /// Returns the exception link register: the address execution resumes at.
+    pub fn elr(&self) -> u64 {
+        self.program_counter
+    }
+
+    /// Sets the exception link register: the address execution resumes at.
+    pub fn set_elr(&mut self, value: u64) {
+        self.program_counter = value;
+    }
+
+    /// Returns the saved program status register.
+    pub fn spsr(&self) -> u64 {
+        self.program_state
+    }
+
+    /// Sets the saved program status register.
+    pub fn set_spsr(&mut self, value: u64) {
+        self.program_state = value;
+    }
+
+    /// Returns SIMD/floating point register `qn` of the interrupted code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than `31`.
+    pub fn q(&self, n: usize) -> u128 {
+        self.floating_point_registers[Self::q_index(n)]
+    }
+
+    /// Sets SIMD/floating point register `qn` of the interrupted code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than `31`.
+    pub fn set_q(&mut self, n: usize, value: u128) {
+        self.floating_point_registers[Self::q_index(n)] = value;
+    }
 }
@@ -4,18 +4,67 @@ use volatile::{WriteVolatile, ReadVolatile};
 
 const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Interrupt {
     Timer1 = 1,
     Timer3 = 3,
     Usb = 9,
+    Aux = 29,
     Gpio0 = 49,
     Gpio1 = 50,
     Gpio2 = 51,
     Gpio3 = 52,
     Uart = 57,
+    ArmTimer = 64,
+    Mailbox = 65,
+    Doorbell0 = 66,
+    Doorbell1 = 67,
+    GpuHalted0 = 68,
+    GpuHalted1 = 69,
+    IllegalAccess1 = 70,
+    IllegalAccess0 = 71,
 }
 
+impl Interrupt {
+    /// The number of the first ARM-specific interrupt. GPU interrupts are
+    /// numbered from `0`; the ARM-specific ones follow, in the order of their
+    /// bits in `IRQ_BASIC_PENDING`.
+    pub const BASIC: usize = 64;
+
+    /// The number of interrupt numbers.
+    pub const MAX: usize = Interrupt::BASIC + 8;
+
+    /// Every interrupt, in order of interrupt number.
+    pub const ALL: [Interrupt; 17] = [
+        Interrupt::Timer1, Interrupt::Timer3, Interrupt::Usb, Interrupt::Aux, Interrupt::Gpio0,
+        Interrupt::Gpio1, Interrupt::Gpio2, Interrupt::Gpio3, Interrupt::Uart,
+        Interrupt::ArmTimer, Interrupt::Mailbox, Interrupt::Doorbell0, Interrupt::Doorbell1,
+        Interrupt::GpuHalted0, Interrupt::GpuHalted1, Interrupt::IllegalAccess1,
+        Interrupt::IllegalAccess0
+    ];
+
+    /// Returns the bit of this interrupt in `IRQ_BASIC_PENDING` and the basic
+    /// enable and disable registers, if it is an ARM-specific interrupt.
+    pub fn basic_bit(&self) -> Option<u32> {
+        match *self as usize {
+            n if n >= Interrupt::BASIC => Some(1 << (n - Interrupt::BASIC)),
+            _ => None
+        }
+    }
+}
+
+/// `IRQ_BASIC_PENDING` bits 0-7: the ARM-specific interrupts (ARM timer,
+/// mailbox, doorbells and GPU halt and illegal access).
+pub const BASIC_ARM_INTERRUPTS: u32 = 0xff;
+
+/// `IRQ_BASIC_PENDING` bits 8 and 9: one or more bits are set in
+/// `IRQ_PENDING[0]` and `IRQ_PENDING[1]` respectively.
+const BASIC_BANKS: [u32; 2] = [1 << 8, 1 << 9];
+
+/// The GPU interrupts mirrored by `IRQ_BASIC_PENDING` bits 10-20. They are
+/// not reflected in bits 8 and 9.
+const BASIC_SHORTCUTS: [u64; 11] = [7, 9, 10, 18, 19, 53, 54, 55, 56, 57, 62];
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -52,18 +101,57 @@ impl Controller {
 
     /// Enables the interrupt `int`.
     pub fn enable(&mut self, int: Interrupt) {
+        if let Some(bit) = int.basic_bit() {
+            return self.registers.ENABLE_BASIC_IRQ.write(bit);
+        }
+
         let (register_num, offset) = Self::interrupt_resgiter_pos(int);
         self.registers.ENABLE_IRQ[register_num].write(1 << offset);
     }
 
     /// Disables the interrupt `int`.
     pub fn disable(&mut self, int: Interrupt) {
+        if let Some(bit) = int.basic_bit() {
+            return self.registers.DISABLE_BASIC_IRQ.write(bit);
+        }
+
         let (register_num, offset) = Self::interrupt_resgiter_pos(int);
         self.registers.DISABLE_IRQ[register_num].write(1 << offset);
     }
 
+    /// Returns the raw value of `IRQ_BASIC_PENDING`.
+    pub fn basic_pending(&self) -> u32 {
+        self.registers.IRQ_BASIC_PENDING.read()
+    }
+
+    /// Returns the pending GPU interrupts, bit `n` set for interrupt number
+    /// `n`. `IRQ_BASIC_PENDING` is read first, and a bank of `IRQ_PENDING` is
+    /// only read if it indicates that the bank has bits set.
+    pub fn pending(&self) -> u64 {
+        let basic = self.basic_pending();
+
+        let mut pending = 0;
+        for (i, &shortcut) in BASIC_SHORTCUTS.iter().enumerate() {
+            if is_bit_set!(basic, 10 + i) {
+                pending |= 1 << shortcut;
+            }
+        }
+
+        for (bank, &bit) in BASIC_BANKS.iter().enumerate() {
+            if basic & bit != 0 {
+                pending |= (self.registers.IRQ_PENDING[bank].read() as u64) << (32 * bank);
+            }
+        }
+
+        pending
+    }
+
     /// Returns `true` if `int` is pending. Otherwise, returns `false`.
     pub fn is_pending(&self, int: Interrupt) -> bool {
+        if let Some(bit) = int.basic_bit() {
+            return self.basic_pending() & bit != 0;
+        }
+
         let (register_num, offset) = Self::interrupt_resgiter_pos(int);
         let register = self.registers.IRQ_PENDING[register_num].read();
         is_bit_set!(register, offset)
@@ -70,6 +70,14 @@ impl MiniUart {
         }
     }
 
+    /// Raises `Interrupt::Aux` while there is at least one byte ready to be
+    /// read. Reading every available byte clears it.
+    pub fn enable_receive_interrupt(&mut self) {
+        // The receive interrupt is bit 0, not bit 1 as documented (see the
+        // BCM2835 errata)
+        self.registers.MU_IER_REG.write(0b1);
+    }
+
     /// Set the read timeout to `milliseconds` milliseconds.
     pub fn set_read_timeout(&mut self, milliseconds: u32) {
         self.timeout = Some(milliseconds);
@@ -0,0 +1,57 @@
+use core::result;
+
+/// The errors a system call can return, with the kernel's `errno`-style
+/// status codes.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// No such process (`ESRCH`).
+    NoProcess = 3,
+    /// I/O error (`EIO`).
+    Io = 5,
+    /// Bad file descriptor (`EBADF`).
+    BadFd = 9,
+    /// Try again, e.g. a futex no longer holds the expected value (`EAGAIN`).
+    Again = 11,
+    /// Out of memory (`ENOMEM`).
+    NoMem = 12,
+    /// A pointer passed to a system call is invalid (`EFAULT`).
+    Fault = 14,
+    /// Resource busy (`EBUSY`).
+    Busy = 16,
+    /// Invalid argument (`EINVAL`).
+    Invalid = 22,
+    /// The reading end of a pipe is closed (`EPIPE`).
+    BrokenPipe = 32,
+    /// Unknown system call (`ENOSYS`).
+    NoSys = 38,
+    /// The operation timed out (`ETIMEDOUT`).
+    TimedOut = 110,
+    /// A status code this library does not know about.
+    Unknown = !0,
+}
+
+/// The result of a system call.
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    /// Converts the status returned in `x7` into a `Result`.
+    pub fn from_status(status: u64) -> Result<()> {
+        use self::Error::*;
+        match status {
+            0 => Ok(()),
+            3 => Err(NoProcess),
+            5 => Err(Io),
+            9 => Err(BadFd),
+            11 => Err(Again),
+            12 => Err(NoMem),
+            14 => Err(Fault),
+            16 => Err(Busy),
+            22 => Err(Invalid),
+            32 => Err(BrokenPipe),
+            38 => Err(NoSys),
+            110 => Err(TimedOut),
+            _ => Err(Unknown)
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Formatted printing over the `write` system call.
+
+use core::fmt;
+
+use syscall::{write_all, Fd};
+
+/// The descriptor of the standard input of every process.
+pub const STDIN: Fd = 0;
+
+/// The descriptor of the standard output of every process.
+pub const STDOUT: Fd = 1;
+
+/// The descriptor of the standard error of every process.
+pub const STDERR: Fd = 2;
+
+/// A `fmt::Write` sink writing to a file descriptor.
+pub struct FdWriter(pub Fd);
+
+impl fmt::Write for FdWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_all(self.0, s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Internal function called by the `print[ln]!` and `eprint[ln]!` macros.
+#[doc(hidden)]
+pub fn _print(fd: Fd, args: fmt::Arguments) {
+    use core::fmt::Write;
+    let _ = FdWriter(fd).write_fmt(args);
+}
+
+/// Like `std::println!`: prints to the standard output, with a newline.
+pub macro println {
+    () => (print!("\n")),
+    ($fmt:expr) => (print!(concat!($fmt, "\n"))),
+    ($fmt:expr, $($arg:tt)*) => (print!(concat!($fmt, "\n"), $($arg)*))
+}
+
+/// Like `std::print!`: prints to the standard output.
+pub macro print($($arg:tt)*) {
+    _print(STDOUT, format_args!($($arg)*))
+}
+
+/// Like `std::eprintln!`: prints to the standard error, with a newline.
+pub macro eprintln {
+    () => (eprint!("\n")),
+    ($fmt:expr) => (eprint!(concat!($fmt, "\n"))),
+    ($fmt:expr, $($arg:tt)*) => (eprint!(concat!($fmt, "\n"), $($arg)*))
+}
+
+/// Like `std::eprint!`: prints to the standard error.
+pub macro eprint($($arg:tt)*) {
+    _print(STDERR, format_args!($($arg)*))
+}
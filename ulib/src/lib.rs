@@ -0,0 +1,40 @@
+#![feature(asm)]
+#![feature(decl_macro)]
+
+#![no_std]
+
+//! User-space library for programs running on the kernel at EL0.
+//!
+//! Programs talk to the kernel exclusively through the system calls wrapped
+//! here; they never need to link against kernel internals.
+
+mod error;
+mod macros;
+pub mod syscall;
+pub mod io;
+
+#[cfg(test)]
+mod tests;
+
+pub use error::{Error, Result};
+pub use syscall::*;
+pub use io::{print, println, eprint, eprintln};
+
+/// Defines the entry point of a user program. `$main` is called with no
+/// arguments and must return the exit code of the process, a `u64`.
+///
+/// ```rust,ignore
+/// ulib::entry!(main);
+///
+/// fn main() -> u64 {
+///     ulib::println!("Hello from EL0!");
+///     0
+/// }
+/// ```
+pub macro entry($main:path) {
+    #[no_mangle]
+    pub extern "C" fn _start() -> ! {
+        let main: fn() -> u64 = $main;
+        $crate::exit(main())
+    }
+}
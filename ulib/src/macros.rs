@@ -0,0 +1,49 @@
+/// Issues system call `$num` with up to six arguments in `x0`-`x5`. Evaluates
+/// to the values of `(x0, x1, x7)` after the call: the return value, an
+/// optional second return value and the status.
+///
+/// Host builds, e.g. of tests, have no kernel to call and panic instead.
+pub macro syscall {
+    ($num:tt) => (syscall!($num, 0, 0, 0, 0, 0, 0)),
+    ($num:tt, $a0:expr) => (syscall!($num, $a0, 0, 0, 0, 0, 0)),
+    ($num:tt, $a0:expr, $a1:expr) => (syscall!($num, $a0, $a1, 0, 0, 0, 0)),
+    ($num:tt, $a0:expr, $a1:expr, $a2:expr) => (syscall!($num, $a0, $a1, $a2, 0, 0, 0)),
+    ($num:tt, $a0:expr, $a1:expr, $a2:expr, $a3:expr) => {
+        syscall!($num, $a0, $a1, $a2, $a3, 0, 0)
+    },
+    ($num:tt, $a0:expr, $a1:expr, $a2:expr, $a3:expr, $a4:expr) => {
+        syscall!($num, $a0, $a1, $a2, $a3, $a4, 0)
+    },
+    ($num:tt, $a0:expr, $a1:expr, $a2:expr, $a3:expr, $a4:expr, $a5:expr) => {{
+        #[cfg(target_arch = "aarch64")]
+        let (x0, x1, x7): (u64, u64, u64);
+        #[cfg(target_arch = "aarch64")]
+        asm!(concat!("mov x0, $3
+                      mov x1, $4
+                      mov x2, $5
+                      mov x3, $6
+                      mov x4, $7
+                      mov x5, $8
+                      svc ", stringify!($num), "
+                      mov $0, x0
+                      mov $1, x1
+                      mov $2, x7")
+             : "=r"(x0), "=r"(x1), "=r"(x7)
+             : "r"($a0 as u64), "r"($a1 as u64), "r"($a2 as u64),
+               "r"($a3 as u64), "r"($a4 as u64), "r"($a5 as u64)
+             : "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "memory"
+             : "volatile");
+        #[cfg(not(target_arch = "aarch64"))]
+        let (x0, x1, x7) = {
+            let _ = ($a0, $a1, $a2, $a3, $a4, $a5);
+            host_syscall($num)
+        };
+        (x0, x1, x7)
+    }}
+}
+
+/// Stands in for `svc` in host builds of `syscall!`, which have no kernel.
+#[cfg(not(target_arch = "aarch64"))]
+pub unsafe fn host_syscall(num: u64) -> (u64, u64, u64) {
+    panic!("system call {} outside of the kernel", num)
+}
@@ -0,0 +1,194 @@
+//! Typed wrappers for every system call. The numbers must match the system
+//! call table of the kernel (`traps::syscall::SYSCALLS`).
+
+use error::{Error, Result};
+use macros::syscall;
+
+/// A file descriptor.
+pub type Fd = usize;
+
+/// Sleeps for `ms` milliseconds (system call 1). Returns the number of
+/// milliseconds that actually elapsed.
+pub fn sleep(ms: u32) -> u32 {
+    let (elapsed, _, _) = unsafe { syscall!(1, ms) };
+    elapsed as u32
+}
+
+/// Creates a pipe (system call 2). Returns the descriptors of its reading and
+/// writing ends, in that order.
+pub fn pipe() -> Result<(Fd, Fd)> {
+    let (read_fd, write_fd, status) = unsafe { syscall!(2) };
+    Error::from_status(status).map(|_| (read_fd as Fd, write_fd as Fd))
+}
+
+/// Reads up to `buf.len()` bytes from `fd` into `buf` (system call 3),
+/// blocking until some data is available. Returns the number of bytes read;
+/// `0` means end of file.
+pub fn read(fd: Fd, buf: &mut [u8]) -> Result<usize> {
+    let (read, _, status) = unsafe { syscall!(3, fd, buf.as_mut_ptr(), buf.len()) };
+    Error::from_status(status).map(|_| read as usize)
+}
+
+/// Writes up to `buf.len()` bytes from `buf` to `fd` (system call 4),
+/// blocking until there is room for some. Returns the number of bytes
+/// written.
+pub fn write(fd: Fd, buf: &[u8]) -> Result<usize> {
+    let (written, _, status) = unsafe { syscall!(4, fd, buf.as_ptr(), buf.len()) };
+    Error::from_status(status).map(|_| written as usize)
+}
+
+/// Writes all of `buf` to `fd`, calling `write` as many times as needed.
+pub fn write_all(fd: Fd, mut buf: &[u8]) -> Result<()> {
+    while !buf.is_empty() {
+        match write(fd, buf)? {
+            0 => return Err(Error::Io),
+            n => buf = &buf[n..]
+        }
+    }
+
+    Ok(())
+}
+
+/// Closes `fd` (system call 5).
+pub fn close(fd: Fd) -> Result<()> {
+    let (_, _, status) = unsafe { syscall!(5, fd) };
+    Error::from_status(status)
+}
+
+/// Terminates the calling process with exit code `code` (system call 6).
+pub fn exit(code: u64) -> ! {
+    unsafe { syscall!(6, code) };
+    loop {}
+}
+
+/// Makes the calling process a real-time process that runs for `budget_us`
+/// microseconds in every `period_us` microseconds (system call 7), or a
+/// best-effort process again if `period_us` is `0`. Fails with
+/// `Error::Invalid` if the budget is `0` or exceeds the period and with
+/// `Error::Busy` if the CPU cannot admit the process.
+pub fn sched_realtime(period_us: u64, budget_us: u64) -> Result<()> {
+    let (_, _, status) = unsafe { syscall!(7, period_us, budget_us) };
+    Error::from_status(status)
+}
+
+/// Maps at least `len` bytes of zeroed memory into the calling process
+/// (system call 8), rounded up to whole pages. Returns the start address of
+/// the mapping, which stays valid until it is passed to `munmap` or the
+/// process exits. This is the building block for a user-space allocator.
+pub fn mmap(len: usize) -> Result<*mut u8> {
+    let (addr, _, status) = unsafe { syscall!(8, len) };
+    Error::from_status(status).map(|_| addr as *mut u8)
+}
+
+/// Unmaps the mapping starting at `addr`, as returned by `mmap` (system call
+/// 9). The whole mapping is freed.
+pub fn munmap(addr: *mut u8) -> Result<()> {
+    let (_, _, status) = unsafe { syscall!(9, addr) };
+    Error::from_status(status)
+}
+
+/// Gives up the rest of the time slice of the calling process (system call
+/// 10).
+pub fn yield_now() {
+    unsafe { syscall!(10) };
+}
+
+/// Returns the ID of the calling process (system call 11).
+pub fn getpid() -> u64 {
+    let (pid, _, _) = unsafe { syscall!(11) };
+    pid
+}
+
+/// Returns the ID of the parent of the calling process, or `0` if it has none
+/// (system call 12).
+pub fn getppid() -> u64 {
+    let (ppid, _, _) = unsafe { syscall!(12) };
+    ppid
+}
+
+/// Returns the number of microseconds since boot (system call 13).
+pub fn uptime_us() -> u64 {
+    let (us, _, _) = unsafe { syscall!(13) };
+    us
+}
+
+/// The clocks `clock_gettime` can read.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    /// Time since boot, which never jumps (`CLOCK_MONOTONIC`).
+    Monotonic = 1,
+}
+
+impl Clock {
+    /// Returns the clock with the `clock_gettime` ID `id`, if it exists.
+    pub fn from_id(id: u64) -> Option<Clock> {
+        match id {
+            1 => Some(Clock::Monotonic),
+            _ => None
+        }
+    }
+}
+
+/// A point in time, as returned by `clock_gettime`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timespec {
+    /// Whole seconds.
+    pub secs: u64,
+    /// Nanoseconds within the second, less than `1_000_000_000`.
+    pub nanos: u64,
+}
+
+/// Reads `clock` (system call 14).
+pub fn clock_gettime(clock: Clock) -> Result<Timespec> {
+    let (secs, nanos, status) = unsafe { syscall!(14, clock as u64) };
+    Error::from_status(status).map(|_| Timespec { secs, nanos })
+}
+
+/// Blocks the calling process on the 32-bit futex word at `addr` if it still
+/// holds `expected` (system call 15), until `futex_wake` is called on `addr`
+/// or `timeout_us` microseconds pass (`0` waits forever). Fails with
+/// `Error::Again` if the word holds another value and with `Error::TimedOut`
+/// if the timeout expired.
+pub fn futex_wait(addr: *const u32, expected: u32, timeout_us: u64) -> Result<()> {
+    let (_, _, status) = unsafe { syscall!(15, addr, expected, timeout_us) };
+    Error::from_status(status)
+}
+
+/// Wakes up to `n` processes blocked on the futex word at `addr` (system call
+/// 16). Returns the number of processes woken.
+pub fn futex_wake(addr: *const u32, n: usize) -> Result<usize> {
+    let (woken, _, status) = unsafe { syscall!(16, addr, n) };
+    Error::from_status(status).map(|_| woken as usize)
+}
+
+/// Creates a disarmed alarm (system call 17) and returns its descriptor.
+/// Reading from it with `read_alarm` blocks until the alarm expires.
+pub fn timer_create() -> Result<Fd> {
+    let (fd, _, status) = unsafe { syscall!(17) };
+    Error::from_status(status).map(|_| fd as Fd)
+}
+
+/// Arms the alarm `fd` to expire in `initial_us` microseconds and then every
+/// `interval_us` microseconds (system call 18). An `initial_us` of `0`
+/// disarms it; an `interval_us` of `0` makes it one-shot.
+pub fn timer_set(fd: Fd, initial_us: u64, interval_us: u64) -> Result<()> {
+    let (_, _, status) = unsafe { syscall!(18, fd, initial_us, interval_us) };
+    Error::from_status(status)
+}
+
+/// Blocks until the alarm `fd` expires and returns the number of times it
+/// expired since it was last read.
+pub fn read_alarm(fd: Fd) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    read(fd, &mut buf)?;
+    Ok(buf.iter().rev().fold(0, |count, &b| (count << 8) | b as u64))
+}
+
+/// Arms a one-shot alarm that expires in `us` microseconds, like `alarm`, and
+/// returns its descriptor.
+pub fn alarm(us: u64) -> Result<Fd> {
+    let fd = timer_create()?;
+    timer_set(fd, us, 0)?;
+    Ok(fd)
+}
@@ -0,0 +1,66 @@
+use error::{Error, Result};
+use syscall::Clock;
+
+const ERRORS: [Error; 11] = [
+    Error::NoProcess, Error::Io, Error::BadFd, Error::Again, Error::NoMem, Error::Fault,
+    Error::Busy, Error::Invalid, Error::BrokenPipe, Error::NoSys,
+    Error::TimedOut,
+];
+
+#[test]
+fn success_status() {
+    assert_eq!(Error::from_status(0), Ok(()));
+}
+
+#[test]
+fn known_status_codes() {
+    assert_eq!(Error::from_status(3), Err(Error::NoProcess));
+    assert_eq!(Error::from_status(5), Err(Error::Io));
+    assert_eq!(Error::from_status(9), Err(Error::BadFd));
+    assert_eq!(Error::from_status(11), Err(Error::Again));
+    assert_eq!(Error::from_status(12), Err(Error::NoMem));
+    assert_eq!(Error::from_status(14), Err(Error::Fault));
+    assert_eq!(Error::from_status(16), Err(Error::Busy));
+    assert_eq!(Error::from_status(22), Err(Error::Invalid));
+    assert_eq!(Error::from_status(32), Err(Error::BrokenPipe));
+    assert_eq!(Error::from_status(38), Err(Error::NoSys));
+    assert_eq!(Error::from_status(110), Err(Error::TimedOut));
+}
+
+#[test]
+fn status_codes_round_trip() {
+    for &error in ERRORS.iter() {
+        assert_ne!(error as u64, 0);
+        assert_eq!(Error::from_status(error as u64), Err(error));
+    }
+}
+
+#[test]
+fn unknown_status_codes() {
+    for &status in [1, 2, 4, 17, 39, 111, 1 << 32, !0].iter() {
+        assert_eq!(Error::from_status(status), Err(Error::Unknown));
+    }
+}
+
+#[test]
+fn result_mapping() {
+    // The wrappers turn the status into a `Result` and attach `x0` on success.
+    let wrap = |x0: u64, x7: u64| -> Result<usize> {
+        Error::from_status(x7).map(|_| x0 as usize)
+    };
+
+    assert_eq!(wrap(42, 0), Ok(42));
+    assert_eq!(wrap(42, 9), Err(Error::BadFd));
+    assert_eq!(wrap(0, 32), Err(Error::BrokenPipe));
+    assert_eq!(wrap(7, 1000), Err(Error::Unknown));
+
+    let chained = Error::from_status(0).and_then(|_| Error::from_status(11));
+    assert_eq!(chained, Err(Error::Again));
+}
+
+#[test]
+fn clock_ids() {
+    assert_eq!(Clock::from_id(Clock::Monotonic as u64), Some(Clock::Monotonic));
+    assert_eq!(Clock::from_id(0), None);
+    assert_eq!(Clock::from_id(2), None);
+}